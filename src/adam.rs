@@ -0,0 +1,172 @@
+use std::path::Path;
+
+use tch::{nn::VarStore, no_grad, TchError, Tensor};
+
+// Adam con weight decay L2 (como torch.optim.Adam). El optimizador de tch no expone
+// sus momentos, asi que se implementa aqui para poder guardarlos en el checkpoint.
+pub struct Adam {
+    // Variables entrenables ordenadas por nombre
+    params: Vec<(String, Tensor)>,
+    m: Vec<Tensor>,
+    v: Vec<Tensor>,
+    step: i64,
+    learning_rate: f64,
+    weight_decay: f64,
+    beta1: f64,
+    beta2: f64,
+    eps: f64,
+}
+
+impl Adam {
+    pub fn new(vs: &VarStore, learning_rate: f64, weight_decay: f64) -> Adam {
+        let mut params: Vec<(String, Tensor)> =
+            vs.variables().into_iter().filter(|(_, t)| t.requires_grad()).collect();
+        params.sort_by(|a, b| a.0.cmp(&b.0));
+
+        let m = params.iter().map(|(_, t)| t.zeros_like()).collect();
+        let v = params.iter().map(|(_, t)| t.zeros_like()).collect();
+
+        Adam {
+            params,
+            m,
+            v,
+            step: 0,
+            learning_rate,
+            weight_decay,
+            beta1: 0.9,
+            beta2: 0.999,
+            eps: 1e-8,
+        }
+    }
+
+    pub fn backward_step(&mut self, loss: &Tensor) {
+        for (_, param) in self.params.iter_mut() {
+            param.zero_grad();
+        }
+        loss.backward();
+        self.step();
+    }
+
+    fn step(&mut self) {
+        self.step += 1;
+        let bias1 = 1.0 - self.beta1.powi(self.step as i32);
+        let bias2 = 1.0 - self.beta2.powi(self.step as i32);
+        let step_size = self.learning_rate / bias1;
+
+        no_grad(|| {
+            for ((_, param), (m, v)) in self.params.iter_mut().zip(self.m.iter_mut().zip(self.v.iter_mut())) {
+                let grad = param.grad();
+                if !grad.defined() {
+                    continue;
+                }
+                let grad = if self.weight_decay > 0.0 { grad + &*param * self.weight_decay } else { grad };
+
+                _ = m.g_mul_scalar_(self.beta1).g_add_(&(&grad * (1.0 - self.beta1)));
+                _ = v.g_mul_scalar_(self.beta2).g_add_(&(&grad * &grad * (1.0 - self.beta2)));
+                let denom = (&*v / bias2).sqrt() + self.eps;
+                _ = param.g_sub_(&(&*m / &denom * step_size));
+            }
+        });
+    }
+
+    // Momentos "m.<variable>" y "v.<variable>" mas el numero de pasos, en un unico fichero
+    pub fn save<P: AsRef<Path>>(&self, ruta: P) -> Result<(), TchError> {
+        let mut named: Vec<(String, Tensor)> = Vec::with_capacity(2 * self.params.len() + 1);
+        named.push(("step".to_owned(), Tensor::from(self.step)));
+
+        for ((name, _), (m, v)) in self.params.iter().zip(self.m.iter().zip(self.v.iter())) {
+            named.push((format!("m.{name}"), m.shallow_clone()));
+            named.push((format!("v.{name}"), v.shallow_clone()));
+        }
+
+        Tensor::save_multi(&named, ruta)
+    }
+
+    pub fn load<P: AsRef<Path>>(&mut self, ruta: P) -> Result<(), TchError> {
+        let named = Tensor::load_multi(ruta)?;
+        let buscar = |clave: &str| {
+            named
+                .iter()
+                .find(|(name, _)| name == clave)
+                .map(|(_, t)| t)
+                .ok_or_else(|| TchError::TensorNameNotFound(clave.to_owned(), "optimizador".to_owned()))
+        };
+
+        let step = buscar("step")?.int64_value(&[]);
+
+        no_grad(|| -> Result<(), TchError> {
+            for ((name, _), (m, v)) in self.params.iter().zip(self.m.iter_mut().zip(self.v.iter_mut())) {
+                m.f_copy_(buscar(&format!("m.{name}"))?)?;
+                v.f_copy_(buscar(&format!("v.{name}"))?)?;
+            }
+            Ok(())
+        })?;
+
+        self.step = step;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use tch::{nn, Device, Kind};
+
+    use super::*;
+    use crate::test_utils::temp_path;
+
+    // Un unico parametro escalar w con perdida (w - 3)^2, cuyo gradiente es 2 (w - 3)
+    fn escalar(valor: f64) -> (VarStore, Tensor) {
+        let vs = VarStore::new(Device::Cpu);
+        let w = vs.root().var("w", &[1], nn::Init::Const(valor));
+        (vs, w)
+    }
+
+    fn perdida(w: &Tensor) -> Tensor {
+        (w - 3.0).square().sum(Kind::Float)
+    }
+
+    #[test]
+    fn step_matches_closed_form() {
+        let (learning_rate, weight_decay) = (0.1, 0.01);
+        let (vs, w) = escalar(1.0);
+        let mut adam = Adam::new(&vs, learning_rate, weight_decay);
+
+        // Dos pasos para comprobar tambien la correccion de sesgo con m y v acumulados
+        let (mut m, mut v, mut esperado) = (0.0, 0.0, 1.0);
+        for t in 1..=2 {
+            adam.backward_step(&perdida(&w));
+
+            let g = 2.0 * (esperado - 3.0) + weight_decay * esperado;
+            m = 0.9 * m + 0.1 * g;
+            v = 0.999 * v + 0.001 * g * g;
+            let m_hat = m / (1.0 - 0.9f64.powi(t));
+            let v_hat = v / (1.0 - 0.999f64.powi(t));
+            esperado -= learning_rate * m_hat / (v_hat.sqrt() + 1e-8);
+
+            assert!((w.double_value(&[0]) - esperado).abs() < 1e-5, "paso {t}: {} != {esperado}", w.double_value(&[0]));
+        }
+    }
+
+    #[test]
+    fn save_load_then_step_matches_uninterrupted() {
+        let (vs_a, w_a) = escalar(1.0);
+        let mut adam_a = Adam::new(&vs_a, 0.1, 0.0);
+        adam_a.backward_step(&perdida(&w_a));
+
+        let ruta = temp_path("adam.pth");
+        adam_a.save(&ruta).unwrap();
+
+        // Un optimizador nuevo sobre una copia del parametro, con los momentos cargados
+        let (vs_b, w_b) = escalar(w_a.double_value(&[0]));
+        let mut adam_b = Adam::new(&vs_b, 0.1, 0.0);
+        adam_b.load(&ruta).unwrap();
+        fs::remove_file(&ruta).unwrap();
+        assert_eq!(adam_b.step, 1);
+
+        adam_a.backward_step(&perdida(&w_a));
+        adam_b.backward_step(&perdida(&w_b));
+        assert_eq!(w_a.double_value(&[0]), w_b.double_value(&[0]));
+    }
+}
@@ -0,0 +1,44 @@
+use std::process::exit;
+
+use chess::{ChessMove, MoveGen};
+use tch::{nn::ModuleT, no_grad, Device};
+
+use crate::{board_controller::board_to_tensor, dqn_nn_model::load_model, evaluator::evaluate, game_manager::GameManager, player::network_value, move_encoding::encode_move};
+
+pub fn analizar(fen: Option<&str>, ruta_nn: &str, top: usize, device: Device) {
+    let game_manager = match fen.map(GameManager::from_fen) {
+        Some(Ok(game_manager)) => game_manager,
+        Some(Err(e)) => {
+            println!("{e}");
+            exit(1);
+        }
+        None => GameManager::new(),
+    };
+    let board = game_manager.board();
+
+    let (_vs, q_network) = match load_model(ruta_nn, device) {
+        Ok(modelo) => modelo,
+        Err(e) => {
+            println!("{e}");
+            exit(1);
+        }
+    };
+
+    let state = game_manager.packed_state();
+
+    println!("FEN: {}", board);
+    println!("Valor: {}", evaluate(&board, 0, None));
+    println!("Valor red (blancas): {:.5}", network_value(&q_network, &game_manager, device));
+
+    let qv = no_grad(|| q_network.forward_t(&board_to_tensor(&state, q_network.encoding()).to_device(device), false))
+        .to_device(Device::Cpu);
+
+    let mut valorados: Vec<(ChessMove, f64)> = MoveGen::new_legal(&board)
+        .map(|m| (m, qv.double_value(&[0, encode_move(m) as i64])))
+        .collect();
+    valorados.sort_by(|a, b| b.1.total_cmp(&a.1));
+
+    for (m, q) in valorados.iter().take(top) {
+        println!("{m}\t{q:.5}");
+    }
+}
@@ -0,0 +1,276 @@
+use std::{fs, process::exit, str::FromStr};
+
+use chess::{ChessMove, Color, GameResult};
+
+use crate::{game_manager::GameManager, model_match::jugar_partida, pgn::result_tag, player::Player};
+
+// Enfrenta dos jugadores en varias partidas alternando colores e informa del resultado desde el punto de vista de A
+pub fn arena(
+    mut jugador_a: Box<dyn Player>,
+    mut jugador_b: Box<dyn Player>,
+    partidas: u32,
+    max_moves: u32,
+    ruta_aperturas: Option<&str>,
+) {
+    let aperturas = match ruta_aperturas {
+        Some(ruta) => match load_openings(ruta) {
+            Ok(aperturas) => aperturas,
+            Err(e) => {
+                println!("{e}");
+                exit(1);
+            }
+        },
+        None => vec![Vec::new()],
+    };
+
+    let mut marcador = Marcador::default();
+
+    for partida in 0..partidas {
+        // Cada apertura se juega dos veces seguidas, una con cada color
+        let apertura = &aperturas[(partida as usize / 2) % aperturas.len()];
+        let a_blancas = partida % 2 == 0;
+
+        let mut game_manager = GameManager::new();
+        for movimiento in apertura {
+            game_manager.do_move(*movimiento);
+        }
+
+        if a_blancas {
+            jugar_partida(&mut game_manager, jugador_a.as_mut(), jugador_b.as_mut(), max_moves);
+        }
+        else {
+            jugar_partida(&mut game_manager, jugador_b.as_mut(), jugador_a.as_mut(), max_moves);
+        }
+
+        let color_a = if a_blancas { Color::White } else { Color::Black };
+        let puntos = score_for(game_manager.result(), color_a);
+        marcador.add(puntos, game_manager.result().is_none());
+
+        println!(
+            "Partida {}/{partidas}: A con {}, {} -> {}",
+            partida + 1,
+            if a_blancas { "blancas" } else { "negras" },
+            describe_result(game_manager.result()),
+            puntos
+        );
+    }
+
+    println!("A: {}", jugador_a.nombre());
+    println!("B: {}", jugador_b.nombre());
+    marcador.print();
+}
+
+// Una apertura por linea en notacion UCI ("e2e4 e7e5 g1f3"); las lineas vacias y con # se ignoran
+fn load_openings(ruta: &str) -> Result<Vec<Vec<ChessMove>>, String> {
+    let contenido = fs::read_to_string(ruta).map_err(|e| format!("No se pudo leer {ruta}: {e}"))?;
+    let mut aperturas = Vec::new();
+
+    for (num_linea, linea) in contenido.lines().enumerate() {
+        let linea = linea.trim();
+        if linea.is_empty() || linea.starts_with('#') {
+            continue;
+        }
+
+        let mut game_manager = GameManager::new();
+        let mut movimientos = Vec::new();
+        for uci in linea.split_whitespace() {
+            let movimiento = match ChessMove::from_str(uci) {
+                Ok(m) if game_manager.board().legal(m) => m,
+                _ => return Err(format!("Movimiento invalido {uci} en {ruta}:{}", num_linea + 1)),
+            };
+            game_manager.do_move(movimiento);
+            movimientos.push(movimiento);
+        }
+
+        if game_manager.result().is_some() {
+            return Err(format!("La apertura de {ruta}:{} termina la partida", num_linea + 1));
+        }
+        aperturas.push(movimientos);
+    }
+
+    if aperturas.is_empty() {
+        return Err(format!("{ruta} no contiene aperturas"));
+    }
+
+    Ok(aperturas)
+}
+
+// 1 victoria, 0.5 tablas, 0 derrota. Sin resultado (limite de movimientos) cuenta como tablas.
+fn score_for(resultado: Option<GameResult>, color: Color) -> f64 {
+    let ganador = match resultado {
+        Some(GameResult::WhiteCheckmates) | Some(GameResult::BlackResigns) => Some(Color::White),
+        Some(GameResult::BlackCheckmates) | Some(GameResult::WhiteResigns) => Some(Color::Black),
+        _ => None,
+    };
+
+    match ganador {
+        Some(c) if c == color => 1.0,
+        Some(_) => 0.0,
+        None => 0.5,
+    }
+}
+
+// Las partidas que llegan a max_moves se adjudican como tablas y se indica en el resultado
+fn describe_result(resultado: Option<GameResult>) -> &'static str {
+    match resultado {
+        Some(_) => result_tag(resultado),
+        None => "1/2-1/2 (adjudicada por limite de movimientos)",
+    }
+}
+
+#[derive(Default)]
+struct Marcador {
+    victorias: u32,
+    tablas: u32,
+    derrotas: u32,
+    // Tablas por llegar a max_moves, incluidas en tablas
+    adjudicadas: u32,
+}
+
+impl Marcador {
+    fn add(&mut self, puntos: f64, adjudicada: bool) {
+        if adjudicada {
+            self.adjudicadas += 1;
+        }
+
+        if puntos == 1.0 {
+            self.victorias += 1;
+        }
+        else if puntos == 0.0 {
+            self.derrotas += 1;
+        }
+        else {
+            self.tablas += 1;
+        }
+    }
+
+    fn partidas(&self) -> u32 {
+        self.victorias + self.tablas + self.derrotas
+    }
+
+    fn score(&self) -> f64 {
+        (self.victorias as f64 + 0.5 * self.tablas as f64) / self.partidas() as f64
+    }
+
+    // Intervalo de confianza del 95% para el score, con la varianza observada por partida
+    fn score_interval(&self) -> (f64, f64) {
+        let n = self.partidas() as f64;
+        let score = self.score();
+        let varianza = (self.victorias as f64 * (1.0 - score).powi(2)
+            + self.tablas as f64 * (0.5 - score).powi(2)
+            + self.derrotas as f64 * score.powi(2))
+            / n;
+        let margen = 1.96 * (varianza / n).sqrt();
+
+        (score - margen, score + margen)
+    }
+
+    fn print(&self) {
+        if self.partidas() == 0 {
+            println!("No se ha jugado ninguna partida");
+            return;
+        }
+
+        let (inferior, superior) = self.score_interval();
+
+        println!("+{} ={} -{} ({} partidas)", self.victorias, self.tablas, self.derrotas, self.partidas());
+        if self.adjudicadas > 0 {
+            println!("{} de las tablas adjudicadas por limite de movimientos", self.adjudicadas);
+        }
+        println!("Score A: {:.1}% [{:.1}%, {:.1}%]", 100.0 * self.score(), 100.0 * inferior, 100.0 * superior);
+        println!(
+            "Elo A - B: {} [{}, {}]",
+            format_elo(elo_difference(self.score())),
+            format_elo(elo_difference(inferior)),
+            format_elo(elo_difference(superior))
+        );
+    }
+}
+
+// Diferencia de Elo que corresponde a un score esperado
+fn elo_difference(score: f64) -> f64 {
+    if score <= 0.0 {
+        f64::NEG_INFINITY
+    }
+    else if score >= 1.0 {
+        f64::INFINITY
+    }
+    else {
+        -400.0 * (1.0 / score - 1.0).log10()
+    }
+}
+
+fn format_elo(elo: f64) -> String {
+    if elo.is_finite() {
+        format!("{elo:+.0}")
+    }
+    else if elo > 0.0 {
+        "+inf".to_owned()
+    }
+    else {
+        "-inf".to_owned()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn marcador(victorias: u32, tablas: u32, derrotas: u32) -> Marcador {
+        Marcador { victorias, tablas, derrotas, adjudicadas: 0 }
+    }
+
+    #[test]
+    fn score_for_each_result() {
+        assert_eq!(score_for(Some(GameResult::WhiteCheckmates), Color::White), 1.0);
+        assert_eq!(score_for(Some(GameResult::WhiteCheckmates), Color::Black), 0.0);
+        assert_eq!(score_for(Some(GameResult::WhiteResigns), Color::Black), 1.0);
+        assert_eq!(score_for(Some(GameResult::BlackCheckmates), Color::White), 0.0);
+        assert_eq!(score_for(Some(GameResult::Stalemate), Color::White), 0.5);
+        assert_eq!(score_for(Some(GameResult::DrawDeclared), Color::Black), 0.5);
+        assert_eq!(score_for(None, Color::White), 0.5);
+        assert_eq!(describe_result(None), "1/2-1/2 (adjudicada por limite de movimientos)");
+        assert_eq!(describe_result(Some(GameResult::BlackCheckmates)), "0-1");
+    }
+
+    #[test]
+    fn marcador_counts_adjudicated_draws() {
+        let mut m = Marcador::default();
+        m.add(1.0, false);
+        m.add(0.5, true);
+        m.add(0.5, false);
+        m.add(0.0, false);
+        assert_eq!((m.victorias, m.tablas, m.derrotas, m.adjudicadas), (1, 2, 1, 1));
+        assert_eq!(m.score(), 0.5);
+    }
+
+    #[test]
+    fn elo_difference_from_score() {
+        assert_eq!(elo_difference(0.5), 0.0);
+        // 400 * log10(3) para un 75%
+        assert!((elo_difference(0.75) - 190.848).abs() < 1e-3);
+        assert!((elo_difference(0.25) + 190.848).abs() < 1e-3);
+        assert_eq!(elo_difference(1.0), f64::INFINITY);
+        assert_eq!(elo_difference(0.0), f64::NEG_INFINITY);
+        assert_eq!(format_elo(190.848), "+191");
+        assert_eq!(format_elo(f64::NEG_INFINITY), "-inf");
+    }
+
+    #[test]
+    fn score_interval_uses_observed_variance() {
+        // Una victoria y una derrota: varianza 0.25 por partida
+        let (inferior, superior) = marcador(1, 0, 1).score_interval();
+        let margen = 1.96 * (0.25f64 / 2.0).sqrt();
+        assert!((inferior - (0.5 - margen)).abs() < 1e-12);
+        assert!((superior - (0.5 + margen)).abs() < 1e-12);
+
+        // Solo tablas: sin varianza el intervalo se reduce al score
+        assert_eq!(marcador(0, 10, 0).score_interval(), (0.5, 0.5));
+
+        // 6 victorias, 2 tablas y 2 derrotas: score 0.7, varianza (6*0.09 + 2*0.04 + 2*0.49) / 10 = 0.16
+        let (inferior, superior) = marcador(6, 2, 2).score_interval();
+        let margen = 1.96 * (0.16f64 / 10.0).sqrt();
+        assert!((inferior - (0.7 - margen)).abs() < 1e-9);
+        assert!((superior - (0.7 + margen)).abs() < 1e-9);
+    }
+}
@@ -4,6 +4,8 @@ use chess::{Piece, Color, Square, Board, Rank, File};
 use crossterm::{execute, terminal::{Clear, ClearType}, cursor::MoveTo};
 use tch::Tensor;
 
+use crate::state_encoding::{batch_to_tensor, PackedState, StateEncoding};
+
 pub fn display_board(board: &Board) {
     match board.side_to_move() {
         Color::Black => display_board_black_side(board),
@@ -97,53 +99,6 @@ fn display_board_white_side(board: &Board) {
     _ = execute!(io::stdout(), Clear(ClearType::CurrentLine), MoveTo(0, fila+1));
 }
 
-pub fn board_to_tensor(board: &Board) -> Tensor {
-    let mut state = [[0; 8]; 9];
-
-    for rank in 0..=7 {
-        for file in 0..=7 {
-            let square = Square::make_square(Rank::from_index(rank), File::from_index(file));
-            let piece_opt = board.piece_on(square);
-
-            if let Some(piece) = piece_opt {
-                let pv = piece.to_index() as i32 + 1;
-
-                state[rank][file] = match board.color_on(square).unwrap() {
-                    Color::Black => -pv,
-                    Color::White => pv,
-                };
-            }
-        }
-    }
-
-    state[8][0] = match board.side_to_move() {
-        Color::Black => -1,
-        Color::White => 1,
-    };
-
-    let my_castle_rights = match board.my_castle_rights() {
-        chess::CastleRights::NoRights => 0,
-        chess::CastleRights::KingSide => 1,
-        chess::CastleRights::QueenSide => 2,
-        chess::CastleRights::Both => 3,
-    };
-
-    let their_castle_rights = match board.their_castle_rights() {
-        chess::CastleRights::NoRights => 0,
-        chess::CastleRights::KingSide => 1,
-        chess::CastleRights::QueenSide => 2,
-        chess::CastleRights::Both => 3,
-    };
-
-    if board.side_to_move() == Color::White {
-        state[8][1] = my_castle_rights;
-        state[8][2] = their_castle_rights;
-    }
-    else {
-        state[8][2] = my_castle_rights;
-        state[8][1] = their_castle_rights;
-    }
-
-    let vector: Vec<i32> = state.iter().flat_map(|f| f.iter()).cloned().collect();
-    Tensor::from_slice(vector.as_slice())
-}
\ No newline at end of file
+pub fn board_to_tensor(state: &PackedState, encoding: StateEncoding) -> Tensor {
+    batch_to_tensor(&[state], encoding).view([-1])
+}
@@ -0,0 +1,186 @@
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+use rand_chacha::ChaCha8Rng;
+use serde::{Deserialize, Serialize};
+use tch::nn::VarStore;
+
+use crate::{adam::Adam, curriculum::CurriculumState, replay_buffer::Replay, train_config::TrainConfig};
+
+// Estado del bucle de entrenamiento. Se guarda al terminar una partida, asi que no hay
+// transiciones pendientes ni acumuladores n-step a medias.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct TrainerState {
+    // Primera partida que falta por jugar; epsilon se recalcula a partir de ella
+    pub next_game: u32,
+    pub total_cont: u32,
+    pub first_log: bool,
+    pub rng: RngState,
+    // Etapa del curriculum de finales y mates recientes
+    #[serde(default)]
+    pub curriculum: CurriculumState,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct RngState {
+    pub seed: [u8; 32],
+    pub stream: u64,
+    pub word_pos: u128,
+}
+
+impl RngState {
+    pub fn from_rng(rng: &ChaCha8Rng) -> RngState {
+        RngState { seed: rng.get_seed(), stream: rng.get_stream(), word_pos: rng.get_word_pos() }
+    }
+
+    pub fn to_rng(&self) -> ChaCha8Rng {
+        let mut rng: ChaCha8Rng = rand::SeedableRng::from_seed(self.seed);
+        rng.set_stream(self.stream);
+        rng.set_word_pos(self.word_pos);
+        rng
+    }
+}
+
+// Directorio con todo lo necesario para continuar un entrenamiento:
+// redes online y target, momentos del optimizador, replay buffer, configuracion y estado del bucle
+pub struct Checkpoint {
+    dir: PathBuf,
+}
+
+const Q_NETWORK: &str = "q_network.pth";
+const TARGET_NETWORK: &str = "target_network.pth";
+const OPTIMIZER: &str = "optimizer.pth";
+const REPLAY: &str = "replay.bin";
+const CONFIG: &str = "config.toml";
+const STATE: &str = "trainer_state.json";
+
+impl Checkpoint {
+    pub fn new<P: AsRef<Path>>(dir: P) -> Checkpoint {
+        Checkpoint { dir: dir.as_ref().to_path_buf() }
+    }
+
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    // El estado se escribe el ultimo: si existe, el resto del checkpoint esta completo
+    pub fn exists(&self) -> bool {
+        self.dir.join(STATE).exists()
+    }
+
+    pub fn config_path(&self) -> PathBuf {
+        self.dir.join(CONFIG)
+    }
+
+    pub fn buffer_path(&self) -> PathBuf {
+        self.dir.join(REPLAY)
+    }
+
+    // config.toml del checkpoint si la red es una de las que guarda
+    pub fn config_for_network(ruta_nn: &Path) -> Option<PathBuf> {
+        let nombre = ruta_nn.file_name()?;
+        if nombre != Q_NETWORK && nombre != TARGET_NETWORK {
+            return None;
+        }
+
+        Some(ruta_nn.with_file_name(CONFIG))
+    }
+
+    pub fn save(
+        &self,
+        config: &TrainConfig,
+        state: &TrainerState,
+        vs_q_network: &VarStore,
+        vs_target_network: &VarStore,
+        optimizador: &Adam,
+        buffer: &dyn Replay,
+    ) -> Result<(), String> {
+        // Un checkpoint anterior deja de ser valido hasta que se termine de escribir este
+        let ruta_estado = self.dir.join(STATE);
+        if ruta_estado.exists() {
+            fs::remove_file(&ruta_estado).map_err(|e| format!("No se pudo borrar {}: {e}", ruta_estado.display()))?;
+        }
+        fs::create_dir_all(&self.dir).map_err(|e| format!("No se pudo crear {}: {e}", self.dir.display()))?;
+
+        config.save(self.config_path())?;
+        vs_q_network.save(self.dir.join(Q_NETWORK)).map_err(|e| e.to_string())?;
+        vs_target_network.save(self.dir.join(TARGET_NETWORK)).map_err(|e| e.to_string())?;
+        optimizador.save(self.dir.join(OPTIMIZER)).map_err(|e| e.to_string())?;
+        buffer
+            .save(&self.buffer_path())
+            .map_err(|e| format!("No se pudo guardar {}: {e}", self.buffer_path().display()))?;
+
+        let contenido = serde_json::to_string_pretty(state).map_err(|e| e.to_string())?;
+        fs::write(&ruta_estado, contenido).map_err(|e| format!("No se pudo escribir {}: {e}", ruta_estado.display()))
+    }
+
+    pub fn load_state(&self) -> Result<TrainerState, String> {
+        let ruta = self.dir.join(STATE);
+        let contenido =
+            fs::read_to_string(&ruta).map_err(|e| format!("No se pudo leer {}: {e}", ruta.display()))?;
+        serde_json::from_str(&contenido).map_err(|e| format!("Estado invalido en {}: {e}", ruta.display()))
+    }
+
+    pub fn load_networks(
+        &self,
+        vs_q_network: &mut VarStore,
+        vs_target_network: &mut VarStore,
+        optimizador: &mut Adam,
+    ) -> Result<(), String> {
+        vs_q_network.load(self.dir.join(Q_NETWORK)).map_err(|e| e.to_string())?;
+        vs_target_network.load(self.dir.join(TARGET_NETWORK)).map_err(|e| e.to_string())?;
+        optimizador.load(self.dir.join(OPTIMIZER)).map_err(|e| e.to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use rand::{Rng, SeedableRng};
+    use rand_chacha::ChaCha8Rng;
+
+    use super::*;
+    use crate::test_utils::temp_path;
+
+    #[test]
+    fn rng_state_restores_same_draws() {
+        let mut rng = ChaCha8Rng::seed_from_u64(7);
+        rng.set_stream(3);
+        for _ in 0..1001 {
+            rng.gen::<u32>();
+        }
+
+        let mut restaurado = RngState::from_rng(&rng).to_rng();
+        let esperado: Vec<u64> = (0..64).map(|_| rng.gen()).collect();
+        let obtenido: Vec<u64> = (0..64).map(|_| restaurado.gen()).collect();
+        assert_eq!(esperado, obtenido);
+    }
+
+    #[test]
+    fn trainer_state_roundtrip() {
+        let mut rng = ChaCha8Rng::seed_from_u64(11);
+        rng.gen::<u64>();
+        let state = TrainerState {
+            next_game: 1234,
+            total_cont: 98765,
+            first_log: false,
+            rng: RngState::from_rng(&rng),
+            curriculum: CurriculumState::default(),
+        };
+
+        let checkpoint = Checkpoint::new(temp_path("checkpoint"));
+        fs::create_dir_all(checkpoint.dir()).unwrap();
+        fs::write(checkpoint.dir().join(STATE), serde_json::to_string_pretty(&state).unwrap()).unwrap();
+        let cargado = checkpoint.load_state();
+        fs::remove_dir_all(checkpoint.dir()).unwrap();
+
+        let cargado = cargado.unwrap();
+        assert_eq!(cargado.next_game, 1234);
+        assert_eq!(cargado.total_cont, 98765);
+        assert!(!cargado.first_log);
+        assert_eq!(cargado.rng.to_rng().gen::<u64>(), rng.gen::<u64>());
+    }
+}
@@ -0,0 +1,385 @@
+use clap::{Args, Parser, Subcommand, ValueEnum};
+
+use crate::{
+    checkpoint::Checkpoint,
+    hybrid_search::HybridMode,
+    mcts::MctsConfig,
+    player::PlayerKind,
+    pretrain::PretrainMode,
+    search::SearchLimits,
+    train_config::{CheckpointConfig, TargetMode, TrainConfig},
+};
+
+#[derive(Parser, Debug)]
+#[command(name = "dqn_chess", version, about = "Motor de ajedrez entrenado con DQN")]
+pub struct Cli {
+    /// Dispositivo: auto, cpu, cuda, cuda:N o mps (por defecto DQN_DEVICE o auto)
+    #[arg(long, global = true)]
+    pub device: Option<String>,
+
+    /// Sin subcomando se muestra el menu interactivo
+    #[command(subcommand)]
+    pub command: Option<Comando>,
+}
+
+// Se construye una sola vez al arrancar: no merece la pena boxear los argumentos de train
+#[allow(clippy::large_enum_variant)]
+#[derive(Subcommand, Debug)]
+pub enum Comando {
+    /// Entrena la red mediante self-play
+    Train(TrainArgs),
+
+    /// Pre-entrena la red o llena el replay buffer con partidas PGN
+    Pretrain(PretrainArgs),
+
+    /// Juega contra la red, el motor alpha-beta o la busqueda hibrida
+    Play {
+        /// Bando del jugador humano
+        #[arg(long, value_enum, default_value_t = Side::White)]
+        side: Side,
+
+        #[arg(long, default_value = "nn.pth")]
+        model: String,
+
+        /// Rival
+        #[arg(long, value_enum, default_value_t = PlayerKind::Network)]
+        opponent: PlayerKind,
+
+        /// Fichero PGN al que se anade la partida
+        #[arg(long, default_value = "games.pgn")]
+        pgn: String,
+
+        /// Posicion de salida en FEN (por defecto la inicial)
+        #[arg(long)]
+        fen: Option<String>,
+
+        #[command(flatten)]
+        search: SearchArgs,
+    },
+
+    /// Muestra la evaluacion y los Q-values de una posicion
+    Analyze {
+        /// Posicion en FEN (por defecto la inicial)
+        #[arg(long)]
+        fen: Option<String>,
+
+        #[arg(long, default_value = "nn.pth")]
+        model: String,
+
+        /// Numero de movimientos a mostrar
+        #[arg(long, default_value_t = 10)]
+        top: usize,
+    },
+
+    /// Enfrenta dos redes en una partida
+    Match {
+        /// Red que juega con blancas
+        #[arg(long, default_value = "nn.pth")]
+        white: String,
+
+        /// Red que juega con negras
+        #[arg(long, default_value = "nn.pth")]
+        black: String,
+
+        /// Limite de movimientos antes de declarar tablas
+        #[arg(long, default_value_t = 300)]
+        max_moves: u32,
+    },
+
+    /// Enfrenta dos jugadores en varias partidas alternando colores y estima la diferencia de Elo
+    Arena {
+        /// Red del jugador evaluado (A)
+        #[arg(long, default_value = "nn.pth")]
+        player_a: String,
+
+        #[arg(long, value_enum, default_value_t = PlayerKind::Network)]
+        kind_a: PlayerKind,
+
+        /// Red del jugador de referencia (B)
+        #[arg(long, default_value = "nn.pth")]
+        player_b: String,
+
+        #[arg(long, value_enum, default_value_t = PlayerKind::Network)]
+        kind_b: PlayerKind,
+
+        /// Numero de partidas
+        #[arg(long, default_value_t = 100)]
+        games: u32,
+
+        /// Limite de movimientos antes de declarar tablas
+        #[arg(long, default_value_t = 300)]
+        max_moves: u32,
+
+        /// Fichero de aperturas: una por linea en UCI ("e2e4 e7e5 g1f3")
+        #[arg(long)]
+        openings: Option<String>,
+
+        /// Limites de los jugadores con busqueda
+        #[command(flatten)]
+        search: SearchArgs,
+    },
+
+    /// Exporta los pesos de la red (.pth, .ot o .safetensors segun la extension)
+    Export {
+        #[arg(long, default_value = "nn.pth")]
+        model: String,
+
+        #[arg(long)]
+        output: String,
+    },
+}
+
+// Limites de los motores de busqueda
+#[derive(Args, Debug)]
+pub struct SearchArgs {
+    /// Profundidad maxima del iterative deepening
+    #[arg(long, default_value_t = 4)]
+    pub depth: u32,
+
+    /// Nodos maximos por movimiento
+    #[arg(long)]
+    pub nodes: Option<u64>,
+
+    /// Uso de la red en la busqueda hibrida
+    #[arg(long, value_enum, default_value_t = HybridMode::Both)]
+    pub hybrid_mode: HybridMode,
+
+    /// Simulaciones MCTS por movimiento
+    #[arg(long, default_value_t = MctsConfig::default().simulations)]
+    pub simulations: u32,
+
+    /// Constante de exploracion de PUCT
+    #[arg(long, default_value_t = MctsConfig::default().c_puct)]
+    pub c_puct: f64,
+
+    /// Temperatura del softmax de los Q-values que da los priors de MCTS
+    #[arg(long, default_value_t = MctsConfig::default().temperature)]
+    pub mcts_temperature: f64,
+}
+
+impl SearchArgs {
+    pub fn limits(&self) -> SearchLimits {
+        SearchLimits { depth: self.depth, nodes: self.nodes }
+    }
+
+    pub fn mcts(&self) -> MctsConfig {
+        MctsConfig {
+            simulations: self.simulations,
+            c_puct: self.c_puct,
+            temperature: self.mcts_temperature,
+            ..Default::default()
+        }
+    }
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug)]
+pub enum Side {
+    White,
+    Black,
+}
+
+impl From<Side> for chess::Color {
+    fn from(side: Side) -> Self {
+        match side {
+            Side::White => chess::Color::White,
+            Side::Black => chess::Color::Black,
+        }
+    }
+}
+
+// Los flags tienen prioridad sobre el fichero de configuracion
+#[derive(Args, Debug)]
+pub struct TrainArgs {
+    /// Fichero de configuracion de entrenamiento (TOML o JSON)
+    #[arg(long)]
+    pub config: Option<String>,
+
+    /// Ruta del checkpoint de la red
+    #[arg(long)]
+    pub model: Option<String>,
+
+    #[arg(long)]
+    pub num_games: Option<u32>,
+
+    /// Semilla del generador aleatorio
+    #[arg(long)]
+    pub seed: Option<u64>,
+
+    #[arg(long)]
+    pub init_epsilon: Option<f64>,
+
+    #[arg(long)]
+    pub final_epsilon: Option<f64>,
+
+    #[arg(long)]
+    pub buffer_capacity: Option<usize>,
+
+    #[arg(long)]
+    pub gamma: Option<f64>,
+
+    #[arg(long, value_enum)]
+    pub target_mode: Option<TargetMode>,
+
+    /// Activa (true) o desactiva (false) Double DQN
+    #[arg(long)]
+    pub double_dqn: Option<bool>,
+
+    #[arg(long)]
+    pub n_step: Option<usize>,
+
+    /// Activa (true) o desactiva (false) prioritized experience replay
+    #[arg(long)]
+    pub prioritized: Option<bool>,
+
+    /// Fichero del replay buffer
+    #[arg(long)]
+    pub buffer_path: Option<String>,
+
+    /// Guarda (true) o no (false) el replay buffer junto a cada checkpoint de la red
+    #[arg(long, num_args = 0..=1, default_missing_value = "true")]
+    pub save_buffer: Option<bool>,
+
+    /// Reanuda (true) o no (false) con el replay buffer guardado
+    #[arg(long, num_args = 0..=1, default_missing_value = "true")]
+    pub resume_buffer: Option<bool>,
+
+    #[arg(long)]
+    pub warmup_size: Option<u32>,
+
+    #[arg(long)]
+    pub train_frequency: Option<u32>,
+
+    #[arg(long)]
+    pub batch_size: Option<usize>,
+
+    #[arg(long)]
+    pub target_update_freq: Option<u32>,
+
+    #[arg(long)]
+    pub learning_rate: Option<f64>,
+
+    #[arg(long)]
+    pub weight_decay: Option<f64>,
+
+    /// Fichero FEN/EPD con las posiciones de salida del self-play
+    #[arg(long)]
+    pub start_positions: Option<String>,
+
+    /// Activa (true) o desactiva (false) el curriculum de finales aleatorios (KQK, KRK, KPK...)
+    #[arg(long, num_args = 0..=1, default_missing_value = "true")]
+    pub curriculum: Option<bool>,
+
+    /// Activa (true) o desactiva (false) MCTS en los movimientos de explotacion del self-play
+    #[arg(long, num_args = 0..=1, default_missing_value = "true")]
+    pub mcts_self_play: Option<bool>,
+
+    /// Simulaciones MCTS por movimiento en self-play
+    #[arg(long)]
+    pub mcts_simulations: Option<u32>,
+
+    /// Peso del valor MCTS en el bootstrap del TD target (0: solo la red target, 1: solo MCTS)
+    #[arg(long)]
+    pub mcts_bootstrap_weight: Option<f64>,
+
+    /// Directorio del checkpoint completo del entrenamiento
+    #[arg(long)]
+    pub checkpoint_dir: Option<String>,
+
+    /// Partidas entre checkpoints (0: solo al terminar)
+    #[arg(long)]
+    pub checkpoint_every: Option<u32>,
+
+    /// Continua (true) o no (false) el entrenamiento desde el checkpoint (y su configuracion si no se indica --config)
+    #[arg(long, num_args = 0..=1, default_missing_value = "true")]
+    pub resume: Option<bool>,
+}
+
+impl TrainArgs {
+    pub fn to_config(&self) -> Result<TrainConfig, String> {
+        let checkpoint = Checkpoint::new(self.checkpoint_dir.as_deref().unwrap_or(&CheckpointConfig::default().dir));
+
+        let mut config = match &self.config {
+            Some(ruta) => TrainConfig::load(ruta)?,
+            None if self.resume == Some(true) && checkpoint.config_path().exists() => TrainConfig::load(checkpoint.config_path())?,
+            None => TrainConfig::default(),
+        };
+
+        if let Some(v) = &self.model { config.model_path = v.clone(); }
+        if let Some(v) = self.num_games { config.num_games = v; }
+        if let Some(v) = self.seed { config.seed = Some(v); }
+        if let Some(v) = self.init_epsilon { config.init_epsilon = v; }
+        if let Some(v) = self.final_epsilon { config.final_epsilon = v; }
+        if let Some(v) = self.buffer_capacity { config.buffer_capacity = v; }
+        if let Some(v) = self.gamma { config.gamma = v; }
+        if let Some(v) = self.target_mode { config.target_mode = v; }
+        if let Some(v) = self.double_dqn { config.double_dqn = v; }
+        if let Some(v) = self.n_step { config.n_step = v; }
+        if let Some(v) = self.prioritized { config.replay.prioritized = v; }
+        if let Some(v) = &self.buffer_path { config.replay.buffer_path = v.clone(); }
+        if let Some(v) = self.save_buffer { config.replay.save_buffer = v; }
+        if let Some(v) = self.resume_buffer { config.replay.resume_buffer = v; }
+        if let Some(v) = self.warmup_size { config.warmup_size = v; }
+        if let Some(v) = self.train_frequency { config.train_frequency = v; }
+        if let Some(v) = self.batch_size { config.batch_size = v; }
+        if let Some(v) = self.target_update_freq { config.target_update_freq = v; }
+        if let Some(v) = self.learning_rate { config.learning_rate = v; }
+        if let Some(v) = self.weight_decay { config.weight_decay = v; }
+        if let Some(v) = &self.start_positions { config.start_positions.path = v.clone(); }
+        if let Some(v) = self.curriculum { config.curriculum.enabled = v; }
+        if let Some(v) = self.mcts_self_play { config.mcts.self_play = v; }
+        if let Some(v) = self.mcts_simulations { config.mcts.simulations = v; }
+        if let Some(v) = self.mcts_bootstrap_weight { config.mcts.bootstrap_weight = v; }
+        if let Some(v) = &self.checkpoint_dir { config.checkpoint.dir = v.clone(); }
+        if let Some(v) = self.checkpoint_every { config.checkpoint.every_games = v; }
+        if let Some(v) = self.resume { config.checkpoint.resume = v; }
+
+        config.validate()?;
+        Ok(config)
+    }
+}
+
+#[derive(Args, Debug)]
+pub struct PretrainArgs {
+    /// Ficheros PGN con las partidas
+    #[arg(long, required = true, num_args = 1..)]
+    pub pgn: Vec<String>,
+
+    /// Ajustar la red (fit), llenar el replay buffer (seed) o ambos
+    #[arg(long, value_enum, default_value_t = PretrainMode::Fit)]
+    pub mode: PretrainMode,
+
+    /// Pasadas sobre las partidas en modo fit
+    #[arg(long, default_value_t = 1)]
+    pub epochs: u32,
+
+    /// Numero maximo de partidas a importar
+    #[arg(long)]
+    pub max_games: Option<usize>,
+
+    /// Configuracion de entrenamiento: arquitectura, optimizador, gamma y replay buffer
+    #[arg(long)]
+    pub config: Option<String>,
+
+    /// Ruta de la red
+    #[arg(long)]
+    pub model: Option<String>,
+
+    /// Fichero del replay buffer
+    #[arg(long)]
+    pub buffer_path: Option<String>,
+}
+
+impl PretrainArgs {
+    pub fn to_config(&self) -> Result<TrainConfig, String> {
+        let mut config = match &self.config {
+            Some(ruta) => TrainConfig::load(ruta)?,
+            None => TrainConfig::default(),
+        };
+
+        if let Some(v) = &self.model { config.model_path = v.clone(); }
+        if let Some(v) = &self.buffer_path { config.replay.buffer_path = v.clone(); }
+
+        config.validate()?;
+        Ok(config)
+    }
+}
@@ -1,37 +1,67 @@
-use std::fs::File;
+use std::{fs::{self, File}, path::Path};
 use serde::Serialize;
 
+// Una fila de registro.csv; el orden de los campos es el de las columnas
 #[derive(Debug, Serialize)]
-struct Registro {
-    game: u32,
-    step: u32,
-    td_target: f64,
-    q_values_action: f64,
-    loss: f64,
-    epsilon: f64,
-    steps_secs: f32,
+pub struct Registro {
+    pub game: u32,
+    pub step: u32,
+    pub td_target: f64,
+    pub q_values_action: f64,
+    pub loss: f64,
+    pub epsilon: f64,
+    pub steps_secs: f32,
+    pub double_dqn: bool,
 }
 
-pub fn write_to_csv(game: u32, step: u32, td_target: f64, q_values_action: f64, loss: f64, epsilon: f64, steps_secs: f32, first_log: bool) {
-    let registro = Registro {
-        game: game,
-        step: step,
-        td_target: td_target,
-        q_values_action: q_values_action,
-        epsilon: epsilon,
-        steps_secs: steps_secs,    
-        loss: loss,
-    };
+const REGISTRO: &str = "registro.csv";
+const CABECERA_REGISTRO: [&str; 8] = ["game", "step", "td_target", "q_values_action", "loss", "epsilon", "steps_secs", "double_dqn"];
+
+// first_log: primera fila del entrenamiento. Un registro.csv con otras columnas (de una version
+// anterior) se aparta a registro.N.csv para no mezclar filas de distinta longitud.
+pub fn write_to_csv(registro: &Registro, first_log: bool) {
+    let cabecera = CABECERA_REGISTRO.join(";");
+    if first_log && fs::read_to_string(REGISTRO).is_ok_and(|c| c.lines().next().is_some_and(|l| l != cabecera)) {
+        let antiguo = (1..).map(|n| format!("registro.{n}.csv")).find(|r| !Path::new(r).exists()).unwrap();
+        fs::rename(REGISTRO, &antiguo).unwrap();
+        println!("{REGISTRO} tenia otras columnas, se ha movido a {antiguo}");
+    }
 
-    let file = File::options().append(true).create(true).open("registro.csv").unwrap();
+    let nuevo = !Path::new(REGISTRO).exists();
+    let file = File::options().append(true).create(true).open(REGISTRO).unwrap();
 
     let mut wtr = csv::WriterBuilder::new().delimiter(b';').has_headers(false).from_writer(file);
 
-    if first_log {
-        wtr.write_record(&["game", "step", "td_target", "q_values_action", "loss", "epsilon", "steps_secs"]).unwrap();
+    if nuevo {
+        wtr.write_record(CABECERA_REGISTRO).unwrap();
     }
 
     wtr.serialize(registro).unwrap();
 
     wtr.flush().unwrap();
 }
+
+#[derive(Debug, Serialize)]
+struct CambioEtapa<'a> {
+    game: u32,
+    from: &'a str,
+    to: &'a str,
+    mate_rate: f64,
+}
+
+// Cada paso de etapa del curriculum de finales
+pub fn write_stage_to_csv(game: u32, from: &str, to: &str, mate_rate: f64) {
+    let ruta = "curriculum.csv";
+    let nuevo = !Path::new(ruta).exists();
+    let file = File::options().append(true).create(true).open(ruta).unwrap();
+
+    let mut wtr = csv::WriterBuilder::new().delimiter(b';').has_headers(false).from_writer(file);
+
+    if nuevo {
+        wtr.write_record(["game", "from", "to", "mate_rate"]).unwrap();
+    }
+
+    wtr.serialize(CambioEtapa { game, from, to, mate_rate }).unwrap();
+
+    wtr.flush().unwrap();
+}
@@ -0,0 +1,389 @@
+use std::collections::{BTreeMap, VecDeque};
+
+use chess::{Board, BoardBuilder, CastleRights, Color, GameResult, MoveGen, Piece, Rank, Square, ALL_SQUARES};
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+
+use crate::game_manager::GameManager;
+
+// Etapas del curriculum: piezas del bando fuerte y del debil, ademas de los reyes
+const STAGES: [(&str, &[Piece], &[Piece]); 6] = [
+    ("KQK", &[Piece::Queen], &[]),
+    ("KRK", &[Piece::Rook], &[]),
+    ("KPK", &[Piece::Pawn], &[]),
+    ("KBBK", &[Piece::Bishop, Piece::Bishop], &[]),
+    ("KQKR", &[Piece::Queen], &[Piece::Rook]),
+    ("KRPKR", &[Piece::Rook, Piece::Pawn], &[Piece::Rook]),
+];
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct CurriculumConfig {
+    pub enabled: bool,
+    // Proporcion de mates del bando fuerte en la ventana para pasar a la siguiente etapa
+    pub mate_rate: f64,
+    // Partidas recientes con las que se mide la proporcion de mates
+    pub window: usize,
+    // Superada la ultima etapa se vuelve a las posiciones normales de salida
+    pub graduate: bool,
+    // mate_rate propio de algunas etapas, por nombre (KRPKR = 0.6)
+    pub stage_mate_rate: BTreeMap<String, f64>,
+}
+
+impl Default for CurriculumConfig {
+    fn default() -> Self {
+        CurriculumConfig {
+            enabled: false,
+            mate_rate: 0.8,
+            window: 200,
+            graduate: true,
+            stage_mate_rate: BTreeMap::new(),
+        }
+    }
+}
+
+impl CurriculumConfig {
+    pub fn validate(&self) -> Vec<String> {
+        let mut errores: Vec<String> = Vec::new();
+
+        if !(0.0..=1.0).contains(&self.mate_rate) {
+            errores.push("curriculum.mate_rate debe estar entre 0 y 1".to_owned());
+        }
+        if self.window == 0 {
+            errores.push("curriculum.window debe ser mayor que 0".to_owned());
+        }
+        for (etapa, rate) in self.stage_mate_rate.iter() {
+            if !STAGES.iter().any(|(nombre, _, _)| nombre == etapa) {
+                errores.push(format!("curriculum.stage_mate_rate: etapa desconocida {etapa}"));
+            }
+            if !(0.0..=1.0).contains(rate) {
+                errores.push(format!("curriculum.stage_mate_rate.{etapa} debe estar entre 0 y 1"));
+            }
+        }
+
+        errores
+    }
+}
+
+// Progreso del curriculum; se guarda en el checkpoint para reanudar en la misma etapa
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct CurriculumState {
+    pub stage: usize,
+    // Si el bando fuerte dio mate en cada una de las ultimas partidas de la etapa
+    pub mates: VecDeque<bool>,
+}
+
+pub struct Curriculum {
+    config: CurriculumConfig,
+    state: CurriculumState,
+}
+
+impl Curriculum {
+    pub fn new(config: &CurriculumConfig, state: CurriculumState) -> Curriculum {
+        Curriculum { config: config.clone(), state }
+    }
+
+    pub fn state(&self) -> &CurriculumState {
+        &self.state
+    }
+
+    // Terminadas todas las etapas con graduate, las partidas salen de las posiciones normales
+    pub fn finished(&self) -> bool {
+        self.state.stage >= STAGES.len()
+    }
+
+    pub fn stage_name(&self) -> &'static str {
+        STAGES.get(self.state.stage).map_or("completo", |(nombre, _, _)| nombre)
+    }
+
+    pub fn mate_rate(&self) -> f64 {
+        if self.state.mates.is_empty() {
+            return 0.0;
+        }
+        self.state.mates.iter().filter(|m| **m).count() as f64 / self.state.mates.len() as f64
+    }
+
+    // Posicion aleatoria de la etapa actual y color del bando fuerte
+    pub fn sample<R: Rng>(&self, rng: &mut R) -> (GameManager, Color) {
+        let (_, fuertes, debiles) = STAGES[self.state.stage.min(STAGES.len() - 1)];
+        let fuerte = if rng.gen::<bool>() { Color::White } else { Color::Black };
+
+        loop {
+            if let Some(board) = random_board(fuertes, debiles, fuerte, rng) {
+                return (GameManager::from_board(board, 0), fuerte);
+            }
+        }
+    }
+
+    // Anota la partida y devuelve true si se pasa a la siguiente etapa
+    pub fn record(&mut self, fuerte: Color, resultado: Option<GameResult>) -> bool {
+        if self.finished() {
+            return false;
+        }
+
+        let mate = matches!(
+            (fuerte, resultado),
+            (Color::White, Some(GameResult::WhiteCheckmates)) | (Color::Black, Some(GameResult::BlackCheckmates))
+        );
+        self.state.mates.push_back(mate);
+        if self.state.mates.len() > self.config.window {
+            self.state.mates.pop_front();
+        }
+
+        let ultima = self.state.stage + 1 == STAGES.len();
+        let objetivo = self.config.stage_mate_rate.get(self.stage_name()).copied().unwrap_or(self.config.mate_rate);
+        if self.state.mates.len() < self.config.window || self.mate_rate() < objetivo || (ultima && !self.config.graduate) {
+            return false;
+        }
+
+        self.state.stage += 1;
+        self.state.mates.clear();
+        true
+    }
+}
+
+// Coloca reyes y piezas en casillas al azar; None si la posicion no es valida o ya esta terminada
+fn random_board<R: Rng>(fuertes: &[Piece], debiles: &[Piece], fuerte: Color, rng: &mut R) -> Option<Board> {
+    let mut builder = BoardBuilder::new();
+    let mut colocadas: Vec<(Square, Piece, Color)> = Vec::new();
+
+    let piezas = [(Piece::King, fuerte), (Piece::King, !fuerte)]
+        .into_iter()
+        .chain(fuertes.iter().map(|p| (*p, fuerte)))
+        .chain(debiles.iter().map(|p| (*p, !fuerte)));
+
+    for (pieza, color) in piezas {
+        let casilla = ALL_SQUARES[rng.gen_range(0..64)];
+        let fila_extrema = casilla.get_rank() == Rank::First || casilla.get_rank() == Rank::Eighth;
+        if colocadas.iter().any(|(c, _, _)| *c == casilla) || (pieza == Piece::Pawn && fila_extrema) {
+            return None;
+        }
+
+        builder.piece(casilla, pieza, color);
+        colocadas.push((casilla, pieza, color));
+    }
+
+    // Dos alfiles del mismo color de casilla no pueden dar mate
+    let alfiles: Vec<usize> = colocadas
+        .iter()
+        .filter(|(_, pieza, color)| *pieza == Piece::Bishop && *color == fuerte)
+        .map(|(casilla, _, _)| (casilla.get_rank().to_index() + casilla.get_file().to_index()) % 2)
+        .collect();
+    if alfiles.len() == 2 && alfiles[0] == alfiles[1] {
+        return None;
+    }
+
+    // Los reyes no pueden estar en casillas adyacentes
+    let (rey_a, rey_b) = (colocadas[0].0, colocadas[1].0);
+    let distancia = rey_a.get_rank().to_index().abs_diff(rey_b.get_rank().to_index())
+        .max(rey_a.get_file().to_index().abs_diff(rey_b.get_file().to_index()));
+    if distancia <= 1 {
+        return None;
+    }
+
+    if !pawn_stage_won(&colocadas, fuerte) {
+        return None;
+    }
+
+    let turno = if rng.gen::<bool>() { Color::White } else { Color::Black };
+    builder
+        .side_to_move(turno)
+        .castle_rights(Color::White, CastleRights::NoRights)
+        .castle_rights(Color::Black, CastleRights::NoRights);
+
+    // try_from rechaza las posiciones en las que el bando que no mueve esta en jaque
+    let board = Board::try_from(&builder).ok()?;
+    if MoveGen::new_legal(&board).len() == 0 {
+        return None;
+    }
+
+    Some(board)
+}
+
+// En las etapas con peon, KPK o KRPKR al azar son tablas muchas veces y la etapa no se superaria.
+// Solo se aceptan posiciones en las que el rey fuerte ocupa una casilla clave del peon y este no
+// se puede perder; con torres, ademas, el rey debil tiene que estar fuera del camino del peon.
+fn pawn_stage_won(colocadas: &[(Square, Piece, Color)], fuerte: Color) -> bool {
+    let Some((peon, _, _)) = colocadas.iter().find(|(_, pieza, color)| *pieza == Piece::Pawn && *color == fuerte) else {
+        return true;
+    };
+    let (rey_fuerte, rey_debil) = (colocadas[0].0, colocadas[1].0);
+
+    // Filas y columnas vistas desde el bando fuerte
+    let fila = |casilla: Square| match fuerte {
+        Color::White => casilla.get_rank().to_index() as i32,
+        Color::Black => 7 - casilla.get_rank().to_index() as i32,
+    };
+    let columna = |casilla: Square| casilla.get_file().to_index() as i32;
+    let distancia = |a: Square, b: Square| (fila(a) - fila(b)).abs().max((columna(a) - columna(b)).abs());
+
+    // El peon de torre y el de septima dan demasiadas tablas
+    let fila_peon = fila(*peon);
+    if columna(*peon) == 0 || columna(*peon) == 7 || fila_peon >= 6 {
+        return false;
+    }
+
+    // Casillas clave: dos filas por delante del peon (una o dos desde la quinta), en su columna o las vecinas
+    let avance = fila(rey_fuerte) - fila_peon;
+    let clave = (columna(rey_fuerte) - columna(*peon)).abs() <= 1 && (avance == 2 || (fila_peon >= 4 && avance == 1));
+    if !clave {
+        return false;
+    }
+
+    if distancia(rey_debil, *peon) <= 1 && distancia(rey_fuerte, *peon) > 1 {
+        return false;
+    }
+
+    let delante = fila(rey_debil) > fila_peon && (columna(rey_debil) - columna(*peon)).abs() <= 1;
+    colocadas.len() <= 3 || !delante
+}
+
+#[cfg(test)]
+mod tests {
+    use chess::File;
+    use rand::SeedableRng;
+    use rand_chacha::ChaCha8Rng;
+
+    use super::*;
+
+    fn contar(board: &Board, pieza: Piece, color: Color) -> u32 {
+        (board.pieces(pieza) & board.color_combined(color)).popcnt()
+    }
+
+    fn curriculum(config: &CurriculumConfig, stage: usize) -> Curriculum {
+        Curriculum::new(config, CurriculumState { stage, mates: VecDeque::new() })
+    }
+
+    #[test]
+    fn samples_are_legal_with_stage_material() {
+        let mut rng = ChaCha8Rng::seed_from_u64(5);
+
+        for (stage, (nombre, fuertes, debiles)) in STAGES.iter().enumerate() {
+            let curriculum = curriculum(&CurriculumConfig::default(), stage);
+
+            for _ in 0..100 {
+                let (game_manager, fuerte) = curriculum.sample(&mut rng);
+                let board = game_manager.board();
+
+                assert!(board.is_sane(), "{nombre}: {board}");
+                assert!(game_manager.result().is_none(), "{nombre}: {board}");
+                assert_eq!(board.castle_rights(Color::White), CastleRights::NoRights);
+                assert_eq!(board.castle_rights(Color::Black), CastleRights::NoRights);
+
+                for pieza in [Piece::Pawn, Piece::Knight, Piece::Bishop, Piece::Rook, Piece::Queen] {
+                    let esperadas = |piezas: &[Piece]| piezas.iter().filter(|p| **p == pieza).count() as u32;
+                    assert_eq!(contar(&board, pieza, fuerte), esperadas(fuertes), "{nombre}: {board}");
+                    assert_eq!(contar(&board, pieza, !fuerte), esperadas(debiles), "{nombre}: {board}");
+                }
+                assert_eq!(contar(&board, Piece::King, Color::White), 1);
+                assert_eq!(contar(&board, Piece::King, Color::Black), 1);
+
+                // Los dos alfiles de KBBK van en casillas de distinto color
+                let alfiles = board.pieces(Piece::Bishop) & board.color_combined(fuerte);
+                if alfiles.popcnt() == 2 {
+                    let oscuras = alfiles.filter(|c| (c.get_rank().to_index() + c.get_file().to_index()) % 2 == 0).count();
+                    assert_eq!(oscuras, 1, "{nombre}: {board}");
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn kpk_samples_have_king_on_key_square() {
+        let mut rng = ChaCha8Rng::seed_from_u64(9);
+        let curriculum = curriculum(&CurriculumConfig::default(), 2);
+        assert_eq!(curriculum.stage_name(), "KPK");
+
+        for _ in 0..200 {
+            let (game_manager, fuerte) = curriculum.sample(&mut rng);
+            let board = game_manager.board();
+            let peon = (board.pieces(Piece::Pawn) & board.color_combined(fuerte)).to_square();
+            let rey = board.king_square(fuerte);
+
+            let fila = |casilla: Square| match fuerte {
+                Color::White => casilla.get_rank().to_index() as i32,
+                Color::Black => 7 - casilla.get_rank().to_index() as i32,
+            };
+            let avance = fila(rey) - fila(peon);
+            let columnas = rey.get_file().to_index().abs_diff(peon.get_file().to_index());
+
+            assert!(peon.get_file() != File::A && peon.get_file() != File::H, "{board}");
+            assert!(fila(peon) < 6, "{board}");
+            assert!(columnas <= 1 && (avance == 2 || (fila(peon) >= 4 && avance == 1)), "{board}");
+        }
+    }
+
+    #[test]
+    fn key_square_rule() {
+        let blancas = |rey: Square, peon: Square, rey_debil: Square| {
+            pawn_stage_won(&[(rey, Piece::King, Color::White), (rey_debil, Piece::King, Color::Black), (peon, Piece::Pawn, Color::White)], Color::White)
+        };
+
+        // Peon en e4: casillas clave d6, e6 y f6
+        assert!(blancas(Square::E6, Square::E4, Square::A8));
+        assert!(blancas(Square::D6, Square::E4, Square::H8));
+        assert!(!blancas(Square::E5, Square::E4, Square::A8));
+        assert!(!blancas(Square::C6, Square::E4, Square::A8));
+        // Desde la quinta tambien vale la fila inmediata
+        assert!(blancas(Square::F6, Square::E5, Square::A8));
+        // Peon de torre y peon en septima
+        assert!(!blancas(Square::B6, Square::A4, Square::H8));
+        assert!(!blancas(Square::E8, Square::E7, Square::A1));
+        // El rey debil captura el peon indefenso
+        assert!(!blancas(Square::E6, Square::E4, Square::D3));
+
+        // Las negras cuentan las filas desde su lado: peon en e5, casilla clave e3
+        let negras = pawn_stage_won(
+            &[(Square::E3, Piece::King, Color::Black), (Square::A1, Piece::King, Color::White), (Square::E5, Piece::Pawn, Color::Black)],
+            Color::Black,
+        );
+        assert!(negras);
+    }
+
+    #[test]
+    fn promotes_at_stage_mate_rate_over_window() {
+        let config = CurriculumConfig {
+            enabled: true,
+            window: 10,
+            mate_rate: 0.8,
+            stage_mate_rate: BTreeMap::from([("KQK".to_owned(), 0.5)]),
+            ..Default::default()
+        };
+        let mut curriculum = curriculum(&config, 0);
+
+        // Hasta llenar la ventana no se promociona, aunque todo sean mates
+        for _ in 0..4 {
+            assert!(!curriculum.record(Color::White, Some(GameResult::WhiteCheckmates)));
+        }
+        // El mate del bando debil no cuenta
+        for _ in 0..5 {
+            assert!(!curriculum.record(Color::White, Some(GameResult::BlackCheckmates)));
+        }
+        // Ventana llena con 5 de 10: alcanza el 0.5 de KQK
+        assert!(curriculum.record(Color::Black, Some(GameResult::BlackCheckmates)));
+        assert_eq!(curriculum.stage_name(), "KRK");
+        assert!(curriculum.state().mates.is_empty());
+
+        // KRK usa el mate_rate general: 7 de 10 no basta, la ventana desliza hasta 8 de 10
+        for i in 0..10 {
+            let resultado = if i >= 3 { Some(GameResult::WhiteCheckmates) } else { None };
+            assert!(!curriculum.record(Color::White, resultado));
+        }
+        assert_eq!(curriculum.mate_rate(), 0.7);
+        assert!(!curriculum.record(Color::White, Some(GameResult::DrawDeclared)));
+        assert!(curriculum.record(Color::White, Some(GameResult::WhiteCheckmates)));
+        assert_eq!(curriculum.stage_name(), "KPK");
+    }
+
+    #[test]
+    fn last_stage_graduates_only_if_enabled() {
+        for graduate in [false, true] {
+            let config = CurriculumConfig { window: 2, graduate, ..Default::default() };
+            let mut curriculum = curriculum(&config, STAGES.len() - 1);
+
+            curriculum.record(Color::White, Some(GameResult::WhiteCheckmates));
+            assert_eq!(curriculum.record(Color::White, Some(GameResult::WhiteCheckmates)), graduate);
+            assert_eq!(curriculum.finished(), graduate);
+        }
+    }
+}
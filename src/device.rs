@@ -0,0 +1,68 @@
+use std::env;
+
+use tch::{Cuda, Device};
+
+const DEVICE_ENV_VAR: &str = "DQN_DEVICE";
+
+// Dispositivo preferido: "cpu", "cuda", "cuda:N", "mps" o "auto".
+// Sin preferencia explicita se consulta la variable de entorno DQN_DEVICE.
+pub fn select_device(preferencia: Option<&str>) -> Device {
+    let preferencia = match preferencia {
+        Some(p) => p.to_owned(),
+        None => env::var(DEVICE_ENV_VAR).unwrap_or_else(|_| "auto".to_owned()),
+    };
+
+    match parse_device(&preferencia) {
+        Ok(device) => device,
+        Err(e) => {
+            println!("{e}, usando seleccion automatica");
+            auto_device()
+        }
+    }
+}
+
+pub fn parse_device(nombre: &str) -> Result<Device, String> {
+    let nombre = nombre.trim().to_lowercase();
+
+    match nombre.as_str() {
+        "auto" => Ok(auto_device()),
+        "cpu" => Ok(Device::Cpu),
+        "mps" => {
+            if tch::utils::has_mps() {
+                Ok(Device::Mps)
+            }
+            else {
+                Err("MPS no disponible".to_owned())
+            }
+        }
+        "cuda" => cuda_device(0),
+        _ => match nombre.strip_prefix("cuda:") {
+            Some(index) => match index.parse::<usize>() {
+                Ok(i) => cuda_device(i),
+                Err(_) => Err(format!("Indice CUDA invalido: {index}")),
+            },
+            None => Err(format!("Dispositivo desconocido: {nombre}")),
+        },
+    }
+}
+
+fn cuda_device(index: usize) -> Result<Device, String> {
+    if Cuda::is_available() && (index as i64) < Cuda::device_count() {
+        Ok(Device::Cuda(index))
+    }
+    else {
+        Err(format!("CUDA:{index} no disponible"))
+    }
+}
+
+fn auto_device() -> Device {
+    if Cuda::is_available() {
+        Device::Cuda(0)
+    }
+    else if tch::utils::has_mps() {
+        Device::Mps
+    }
+    else {
+        Device::Cpu
+    }
+}
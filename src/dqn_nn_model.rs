@@ -1,20 +1,106 @@
-use tch::{nn::{self, Module, ModuleT}, Tensor};
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+use tch::{nn::{self, Module, ModuleT}, Device, Tensor};
+
+use crate::{move_encoding::{mask_illegal, NUM_ACTIONS}, state_encoding::StateEncoding, train_config::model_config_for};
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ModelConfig {
+    // Cabeza dueling: Q(s, a) = V(s) + A(s, a) - media(A(s, .))
+    pub dueling: bool,
+    // Codificacion de la entrada: fija el numero de canales de la primera convolucion
+    pub encoding: StateEncoding,
+
+    // Convolucion inicial (sin padding) y max-pool 2x2 opcional
+    pub stem_channels: i64,
+    pub stem_kernel: i64,
+    pub pooling: bool,
+
+    // Canales de salida de cada bloque residual: su longitud es el numero de bloques
+    pub block_channels: Vec<i64>,
+    // Debe ser impar: el padding k/2 mantiene las dimensiones del tablero
+    pub block_kernel: i64,
+
+    // Capas ocultas entre las convoluciones y la salida
+    pub head_hidden: Vec<i64>,
+}
+
+impl Default for ModelConfig {
+    fn default() -> Self {
+        ModelConfig {
+            dueling: false,
+            encoding: StateEncoding::default(),
+            stem_channels: 72,
+            stem_kernel: 3,
+            pooling: true,
+            block_channels: vec![128, 256, 256, 256, 256, 256, 256, 256, 256, 128],
+            block_kernel: 1,
+            head_hidden: Vec::new(),
+        }
+    }
+}
+
+impl ModelConfig {
+    // Alto y ancho de la salida de la parte convolucional
+    fn spatial_size(&self) -> (i64, i64) {
+        let altura = self.encoding.height() - self.stem_kernel + 1;
+        let ancho = 8 - self.stem_kernel + 1;
+
+        if self.pooling {
+            (altura / 2, ancho / 2)
+        }
+        else {
+            (altura, ancho)
+        }
+    }
+
+    fn flatten_size(&self) -> i64 {
+        let (altura, ancho) = self.spatial_size();
+        let canales = self.block_channels.last().copied().unwrap_or(self.stem_channels);
+        canales * altura * ancho
+    }
+
+    pub fn validate(&self) -> Vec<String> {
+        let mut errores: Vec<String> = Vec::new();
+
+        if self.stem_channels <= 0 {
+            errores.push("model.stem_channels debe ser mayor que 0".to_owned());
+        }
+        if self.stem_kernel <= 0 {
+            errores.push("model.stem_kernel debe ser mayor que 0".to_owned());
+        }
+        else {
+            let (altura, ancho) = self.spatial_size();
+            if altura <= 0 || ancho <= 0 {
+                errores.push("model.stem_kernel es demasiado grande para el tablero".to_owned());
+            }
+        }
+        if self.block_channels.iter().any(|c| *c <= 0) {
+            errores.push("model.block_channels debe contener valores mayores que 0".to_owned());
+        }
+        if self.block_kernel <= 0 || self.block_kernel % 2 == 0 {
+            errores.push("model.block_kernel debe ser impar".to_owned());
+        }
+        if self.head_hidden.iter().any(|c| *c <= 0) {
+            errores.push("model.head_hidden debe contener valores mayores que 0".to_owned());
+        }
+
+        errores
+    }
+}
 
 #[derive(Debug)]
 pub struct DQNModelNN {
     conv1: nn::Conv2D,
     bn1: nn::BatchNorm,
-    res_block1: ResidualBlock,
-    res_block2: ResidualBlock,
-    res_block3: ResidualBlock,
-    res_block4: ResidualBlock,
-    res_block5: ResidualBlock,
-    res_block6: ResidualBlock,
-    res_block7: ResidualBlock,
-    res_block8: ResidualBlock,
-    res_block9: ResidualBlock,
-    res_block10: ResidualBlock,
+    pooling: bool,
+    res_blocks: Vec<ResidualBlock>,
+    hidden: Vec<nn::Linear>,
     fc1: nn::Linear,
+    value_fc: Option<nn::Linear>,
+    encoding: StateEncoding,
 }
 
 #[derive(Debug)]
@@ -23,18 +109,24 @@ pub struct ResidualBlock {
     bn1: nn::BatchNorm,
     conv2: nn::Conv2D,
     bn2: nn::BatchNorm,
-    adjust_conv: nn::Conv2D,
-    in_channels: i64,
-    out_channels: i64
+    // Solo cuando cambia el numero de canales
+    adjust_conv: Option<nn::Conv2D>,
 }
 
 impl ResidualBlock {
-    fn new(vs: &nn::Path, in_channels: i64, out_channels: i64) -> ResidualBlock {
-        let conv1 = nn::conv2d(vs, in_channels, out_channels, 1, Default::default());
-        let bn1 = nn::batch_norm2d(vs, out_channels, tch::nn::BatchNormConfig { ..Default::default() });
-        let conv2 = nn::conv2d(vs, out_channels, out_channels, 1, Default::default());
-        let bn2 = nn::batch_norm2d(vs, out_channels, tch::nn::BatchNormConfig { ..Default::default() });
-        let adjust_conv = nn::conv2d(vs, in_channels, out_channels, 1, Default::default());
+    fn new(vs: &nn::Path, in_channels: i64, out_channels: i64, kernel: i64) -> ResidualBlock {
+        let conv_config = nn::ConvConfig { padding: kernel / 2, ..Default::default() };
+
+        let conv1 = nn::conv2d(vs / "conv1", in_channels, out_channels, kernel, conv_config);
+        let bn1 = nn::batch_norm2d(vs / "bn1", out_channels, Default::default());
+        let conv2 = nn::conv2d(vs / "conv2", out_channels, out_channels, kernel, conv_config);
+        let bn2 = nn::batch_norm2d(vs / "bn2", out_channels, Default::default());
+        let adjust_conv = if in_channels != out_channels {
+            Some(nn::conv2d(vs / "adjust", in_channels, out_channels, 1, Default::default()))
+        }
+        else {
+            None
+        };
 
         ResidualBlock {
             conv1,
@@ -42,87 +134,155 @@ impl ResidualBlock {
             conv2,
             bn2,
             adjust_conv,
-            in_channels,
-            out_channels
         }
     }
 
-    fn forward(&self, x: &Tensor) -> Tensor {
-        let residual = if self.in_channels == self.out_channels {
-            x.shallow_clone()
-        } else {
-            self.adjust_conv.forward(x)
+    fn forward_t(&self, x: &Tensor, train: bool) -> Tensor {
+        let residual = match &self.adjust_conv {
+            Some(adjust_conv) => adjust_conv.forward(x),
+            None => x.shallow_clone(),
         };
-        //let residual = x.shallow_clone();
-        let conv1_out = self.conv1.forward(&x);
-        let bn1_out = self.bn1.forward_t(&conv1_out, true).relu();
-        
+        let conv1_out = self.conv1.forward(x);
+        let bn1_out = self.bn1.forward_t(&conv1_out, train).relu();
+
         let conv2_out = self.conv2.forward(&bn1_out);
-        let bn2_out = self.bn2.forward_t(&conv2_out, true);
-        
+        let bn2_out = self.bn2.forward_t(&conv2_out, train);
+
         bn2_out + residual
     }
 }
 
 impl DQNModelNN {
-    pub fn new(vs: &nn::Path) -> DQNModelNN {
-        let conv1 = nn::conv2d(vs, 1, 72, 3, Default::default());
-        let bn1 = nn::batch_norm2d(vs, 72, tch::nn::BatchNormConfig { ..Default::default() });
-
-        let res_block1 = ResidualBlock::new(vs, 72, 128);
-        let res_block2 = ResidualBlock::new(vs, 128, 256);
-        let res_block3 = ResidualBlock::new(vs, 256, 256);
-        let res_block4 = ResidualBlock::new(vs, 256, 256);
-        let res_block5 = ResidualBlock::new(vs, 256, 256);
-        let res_block6 = ResidualBlock::new(vs, 256, 256);
-        let res_block7 = ResidualBlock::new(vs, 256, 256);
-        let res_block8 = ResidualBlock::new(vs, 256, 256);
-        let res_block9 = ResidualBlock::new(vs, 256, 256);
-        let res_block10 = ResidualBlock::new(vs, 256, 128);
-        let fc1 = nn::linear(vs, 1152, 128, Default::default());
+    pub fn new(vs: &nn::Path, config: &ModelConfig) -> DQNModelNN {
+        let conv1 = nn::conv2d(vs / "stem", config.encoding.channels(), config.stem_channels, config.stem_kernel, Default::default());
+        let bn1 = nn::batch_norm2d(vs / "stem_bn", config.stem_channels, Default::default());
+
+        let mut res_blocks = Vec::with_capacity(config.block_channels.len());
+        let mut in_channels = config.stem_channels;
+        for (i, out_channels) in config.block_channels.iter().enumerate() {
+            res_blocks.push(ResidualBlock::new(&(vs / format!("block{i}")), in_channels, *out_channels, config.block_kernel));
+            in_channels = *out_channels;
+        }
+
+        let mut hidden = Vec::with_capacity(config.head_hidden.len());
+        let mut in_features = config.flatten_size();
+        for (i, out_features) in config.head_hidden.iter().enumerate() {
+            hidden.push(nn::linear(vs / format!("hidden{i}"), in_features, *out_features, Default::default()));
+            in_features = *out_features;
+        }
+
+        let fc1 = nn::linear(vs / "q", in_features, NUM_ACTIONS as i64, Default::default());
+        let value_fc = if config.dueling {
+            Some(nn::linear(vs / "value", in_features, 1, Default::default()))
+        }
+        else {
+            None
+        };
 
         DQNModelNN {
             conv1,
             bn1,
-            res_block1,
-            res_block2,
-            res_block3,
-            res_block4,
-            res_block5,
-            res_block6,
-            res_block7,
-            res_block8,
-            res_block9,
-            res_block10,
-            fc1
+            pooling: config.pooling,
+            res_blocks,
+            hidden,
+            fc1,
+            value_fc,
+            encoding: config.encoding,
         }
     }
-}
 
-impl nn::Module for DQNModelNN {
-    fn forward(&self, xs: &Tensor) -> Tensor {
-        let xs = xs.to_kind(tch::Kind::Float).view([-1, 1, 9, 8]).to_device(tch::Device::Mps);
+    pub fn encoding(&self) -> StateEncoding {
+        self.encoding
+    }
+
+    // Valor del estado para el bando que mueve. Sin cabeza dueling se aproxima con el maximo Q
+    // de las acciones legales: las salidas ilegales nunca se entrenan.
+    // Solo se usa en inferencia, asi que batch norm va en modo evaluacion.
+    pub fn state_value(&self, xs: &Tensor, mask: &Tensor) -> Tensor {
+        match &self.value_fc {
+            Some(value_fc) => value_fc.forward(&self.features(xs, false)).squeeze_dim(-1),
+            None => {
+                let q_values = mask_illegal(&self.forward_t(xs, false), &mask.to_device(xs.device()));
+                let (max_q, _) = q_values.max_dim(-1, false);
+                max_q
+            }
+        }
+    }
+
+    // Q y, con cabeza dueling, V en una sola pasada en modo evaluacion
+    pub fn q_and_value(&self, xs: &Tensor) -> (Tensor, Option<Tensor>) {
+        let (q, value) = self.head(&self.features(xs, false));
+        (q, value.map(|v| v.squeeze_dim(-1)))
+    }
+
+    fn head(&self, flattened: &Tensor) -> (Tensor, Option<Tensor>) {
+        let fc1_out = self.fc1.forward(flattened);
 
-        let conv1_out = self.conv1.forward(&xs).max_pool2d_default(2);       
-        let bn1_out = self.bn1.forward_t(&conv1_out, true).relu();
+        match &self.value_fc {
+            Some(value_fc) => {
+                let value = value_fc.forward(flattened);
+                let advantage_mean = fc1_out.mean_dim(-1, true, tch::Kind::Float);
+                (&value + fc1_out - advantage_mean, Some(value))
+            }
+            None => (fc1_out, None),
+        }
+    }
+
+    // train: batch norm usa las estadisticas del lote y actualiza las medias acumuladas;
+    // en evaluacion usa las acumuladas y la salida no depende del resto del lote
+    fn features(&self, xs: &Tensor, train: bool) -> Tensor {
+        let xs = xs.to_kind(tch::Kind::Float).view([-1, self.encoding.channels(), self.encoding.height(), 8]);
+
+        let mut conv1_out = self.conv1.forward(&xs);
+        if self.pooling {
+            conv1_out = conv1_out.max_pool2d_default(2);
+        }
+        let mut out = self.bn1.forward_t(&conv1_out, train).relu();
+
+        for res_block in self.res_blocks.iter() {
+            out = res_block.forward_t(&out, train).relu();
+        }
+
+        let mut out = out.flatten(1, -1);
+        for layer in self.hidden.iter() {
+            out = layer.forward(&out).relu();
+        }
+
+        out
+    }
+}
 
-        let res1_out = self.res_block1.forward(&bn1_out).relu();
-        let res2_out = self.res_block2.forward(&res1_out).relu();
-        let res3_out = self.res_block3.forward(&res2_out).relu();
-        let res4_out = self.res_block4.forward(&res3_out).relu();
-        let res5_out = self.res_block5.forward(&res4_out).relu();
-        let res6_out = self.res_block6.forward(&res5_out).relu();
-        let res7_out = self.res_block7.forward(&res6_out).relu();
-        let res8_out = self.res_block8.forward(&res7_out).relu();
-        let res9_out = self.res_block9.forward(&res8_out).relu();
-        let res10_out = self.res_block10.forward(&res9_out).relu();
+// La arquitectura sale de la configuracion guardada junto a la red; si no coincide con los
+// pesos el error dice de donde se leyo
+pub fn load_model<P: AsRef<Path>>(ruta: P, device: Device) -> Result<(nn::VarStore, DQNModelNN), String> {
+    let ruta = ruta.as_ref();
+    if !ruta.exists() {
+        return Err(format!("No se encontro la red neuronal {}", ruta.display()));
+    }
+
+    let (config, origen) = match model_config_for(ruta)? {
+        Some((config, ruta_config)) => (config, format!("la arquitectura de {}", ruta_config.display())),
+        None => (
+            ModelConfig::default(),
+            format!("la arquitectura por defecto (no hay {} junto a la red)", ruta.with_extension("train.toml").display()),
+        ),
+    };
 
-        let [batch_size, num_channels, altura, ancho]: [i64; 4] = res10_out.size4().unwrap().into();
-        let flattened = res10_out.view([batch_size, num_channels * altura * ancho]);
+    let mut vs = nn::VarStore::new(device);
+    let q_network = DQNModelNN::new(&vs.root(), &config);
+    vs.load(ruta).map_err(|e| format!("No se pudo cargar {} con {origen}: {e}", ruta.display()))?;
 
-        let fc1_out = self.fc1.forward(&flattened);
+    Ok((vs, q_network))
+}
 
-        fc1_out
+pub fn export_model<P: AsRef<Path>>(ruta: P, salida: P, device: Device) -> Result<(), String> {
+    let (vs, _q_network) = load_model(ruta, device)?;
+    vs.save(salida).map_err(|e| e.to_string())
+}
 
+impl nn::ModuleT for DQNModelNN {
+    fn forward_t(&self, xs: &Tensor, train: bool) -> Tensor {
+        let (q, _value) = self.head(&self.features(xs, train));
+        q
     }
 }
@@ -1,185 +1,304 @@
-use std::{io, path::Path, str::FromStr, time::Instant};
+use std::{io, path::Path, process::exit, time::Instant};
 
-use chess::{Board, ChessMove, Color};
+use chess::ChessMove;
 use crossterm::{cursor::MoveTo, execute};
-use rand::Rng;
+use rand::{Rng, SeedableRng};
+use rand_chacha::ChaCha8Rng;
 use tch::{
-    nn::{self, Module, OptimizerConfig},
-    no_grad, Tensor,
+    nn::{self, ModuleT},
+    no_grad, Device, Tensor,
 };
 
 use crate::{
+    adam::Adam,
     board_controller::{board_to_tensor, display_board},
-    csv_manager::write_to_csv,
+    checkpoint::{Checkpoint, RngState, TrainerState},
+    csv_manager::{write_stage_to_csv, write_to_csv, Registro},
+    curriculum::{Curriculum, CurriculumState},
     dqn_nn_model::DQNModelNN,
-    evaluator::evaluate,
+    evaluator::{evaluate, evaluate_relative},
     game_manager::GameManager,
-    replay_buffer::{BufferElement, ReplayBuffer},
+    mcts,
+    n_step::GameTransitions,
+    pgn::{append_pgn, game_to_pgn, PgnHeaders},
+    move_encoding::{decode_move, encode_move, legal_mask, legal_mask_batch, mask_illegal},
+    prioritized_replay_buffer::PrioritizedReplayBuffer,
+    replay_buffer::{BufferElement, Replay, ReplayBuffer},
+    start_positions::StartPositions,
+    state_encoding::{batch_to_tensor, PackedState},
+    train_config::{TargetMode, TrainConfig},
 };
 
-pub fn dqn_train() {
-    let ruta_nn = "nn.pth";
+pub fn dqn_train(config: &TrainConfig, device: Device) {
+    let ruta_nn = config.model_path.as_str();
     let load_path = Path::new(ruta_nn);
 
-    let num_games = 10000;
+    let num_games = config.num_games;
+
+    if let Err(e) = config.save(config.dump_path()) {
+        println!("{e}");
+    }
+
+    let checkpoint = Checkpoint::new(&config.checkpoint.dir);
+    let estado = if config.checkpoint.resume && checkpoint.exists() {
+        match checkpoint.load_state() {
+            Ok(estado) => Some(estado),
+            Err(e) => {
+                println!("{e}");
+                exit(1);
+            }
+        }
+    }
+    else {
+        if config.checkpoint.resume {
+            println!("No hay checkpoint en {}, se empieza desde cero", checkpoint.dir().display());
+        }
+        None
+    };
 
     // Calculo de epsilon
-    let init_epsilon: f64 = 1.0;
-    let final_epsilon: f64 = 0.01;
+    let init_epsilon = config.init_epsilon;
+    let final_epsilon = config.final_epsilon;
 
     // replayBuffer
-    let capacity: usize = 500000;
-    let mut buffer = ReplayBuffer::new(capacity);
-    let gamma = 0.99;
+    let mut buffer = match estado {
+        Some(_) => match load_buffer(&checkpoint.buffer_path(), config) {
+            Ok(buffer) => buffer,
+            Err(e) => {
+                println!("No se pudo cargar {}: {e}", checkpoint.buffer_path().display());
+                exit(1);
+            }
+        },
+        None => create_buffer(config),
+    };
+    let gamma = config.gamma;
+    let gamma_n = gamma.powi(config.n_step as i32);
 
     // entrenamiento de target network
-    let valor_minimo_entrenar = 35000;
-    let frecuencia_entrenamiento = 10;
+    let valor_minimo_entrenar = config.warmup_size;
+    let frecuencia_entrenamiento = config.train_frequency;
 
-    let samples_size: usize = 16;
+    let samples_size = config.batch_size;
+    let encoding = config.model.encoding;
+    let buffer_path = Path::new(&config.replay.buffer_path);
 
     // Redes neuronales
-    let mut vs_q_network = nn::VarStore::new(tch::Device::Mps);
-    let q_network = DQNModelNN::new(&vs_q_network.root());
-    let mut vs_target_network = nn::VarStore::new(tch::Device::Mps);
-    let target_network = DQNModelNN::new(&vs_target_network.root());
-
-    if load_path.exists() {
-        vs_q_network.load(load_path).unwrap();
-    }
+    let mut vs_q_network = nn::VarStore::new(device);
+    let q_network = DQNModelNN::new(&vs_q_network.root(), &config.model);
+    let mut vs_target_network = nn::VarStore::new(device);
+    let target_network = DQNModelNN::new(&vs_target_network.root(), &config.model);
 
-    let learning_rate = 0.001;
-    let weight_decay = 0.01;
     //let mut optimizador = tch::nn::RmsProp::default().build(&vs_q_network, learning_rate).unwrap();
-    let mut optimizador = tch::nn::Adam::default()
-        .build(&vs_q_network, learning_rate)
-        .unwrap();
-    optimizador.set_weight_decay(weight_decay);
-    let target_network_update_freq = 10000;
+    let mut optimizador = Adam::new(&vs_q_network, config.learning_rate, config.weight_decay);
+    let target_network_update_freq = config.target_update_freq;
 
     let start_time = Instant::now();
     let mut first_log = true;
+    let mut total_cont: u32 = 0;
+    let mut start_game: u32 = 0;
+    let mut rng = match config.seed {
+        Some(seed) => ChaCha8Rng::seed_from_u64(seed),
+        None => ChaCha8Rng::from_entropy(),
+    };
+    let mut curriculum_state = CurriculumState::default();
+
+    match &estado {
+        Some(estado) => {
+            if let Err(e) = checkpoint.load_networks(&mut vs_q_network, &mut vs_target_network, &mut optimizador) {
+                println!("{e}");
+                exit(1);
+            }
 
-    _ = vs_target_network.copy(&vs_q_network).unwrap();
+            first_log = estado.first_log;
+            total_cont = estado.total_cont;
+            start_game = estado.next_game;
+            rng = estado.rng.to_rng();
+            curriculum_state = estado.curriculum.clone();
+            println!("Reanudando en la partida {start_game} ({total_cont} pasos)");
+        }
+        None => {
+            if load_path.exists() {
+                vs_q_network.load(load_path).unwrap();
+            }
 
-    let mut rng = rand::thread_rng();
-    let mut total_cont: u32 = 0;
+            _ = vs_target_network.copy(&vs_q_network).unwrap();
+        }
+    }
 
-    for game_step in 0..num_games {
-        let mut game_manager = GameManager::new();
+    let posiciones = match StartPositions::load(&config.start_positions) {
+        Ok(posiciones) => posiciones,
+        Err(e) => {
+            println!("{e}");
+            exit(1);
+        }
+    };
+
+    let mut curriculum = config.curriculum.enabled.then(|| Curriculum::new(&config.curriculum, curriculum_state));
+
+    for game_step in start_game..num_games {
+        // Con curriculum la partida sale de un final de la etapa actual; fuerte es el bando con ventaja
+        let mut fuerte = None;
+        let mut game_manager = match (&curriculum, &posiciones) {
+            (Some(curriculum), _) if !curriculum.finished() => {
+                let (game_manager, color) = curriculum.sample(&mut rng);
+                fuerte = Some(color);
+                game_manager
+            }
+            (_, Some(posiciones)) => posiciones.sample(&mut rng),
+            _ => GameManager::new(),
+        };
         let mut game_result: Option<chess::GameResult> = game_manager.result();
         let mut game_cont: u32 = 0;
-        let mut force_random = false;
+
+        let mut transiciones = GameTransitions::new(config);
 
         // Para log
         let mut random_moves = 0;
 
         let epsilon = get_epsilon(game_step, init_epsilon, final_epsilon, num_games);
 
+        // Partidas de muestra para self_play.pgn, comentadas con el origen de cada movimiento
+        let guardar_pgn = config.pgn.every_games > 0 && (game_step + 1) % config.pgn.every_games == 0;
+        let mut comentarios: Vec<Option<String>> = Vec::new();
+
         while game_result.is_none() {
             let mut moves = game_manager.get_moves();
-            let mut action_index  = 127;
+            let actual_board = game_manager.board();
+            let actual_state = game_manager.packed_state();
+            let movimiento: ChessMove;
+            let origen: String;
+            let mut next_value = None;
 
-            if rng.gen::<f64>() < epsilon || force_random {
-                action_index = rng.gen_range(0..moves.len());
+            if rng.gen::<f64>() < epsilon {
+                movimiento = moves.nth(rng.gen_range(0..moves.len())).unwrap();
                 random_moves += 1;
+                origen = "random".to_owned();
             } 
+            else if config.mcts.self_play {
+                let result = mcts::search(&q_network, device, &game_manager, &config.mcts);
+                movimiento = result.best_move;
+                next_value = result.next_value.map(|v| v as f32);
+                origen = "mcts".to_owned();
+            }
             else {
-                let qv = q_network.forward(&board_to_tensor(&game_manager.board()));
-                let filtered_output = qv.softmax(-1, tch::Kind::Float);
-                action_index = filtered_output.argmax(None, false).int64_value(&[]) as usize;    
+                let mask = legal_mask(&game_manager.legal_actions()).to_device(device);
+                let (action, q) = no_grad(|| {
+                    let qv = q_network.forward_t(&board_to_tensor(&actual_state, encoding).to_device(device), false);
+                    let qv = mask_illegal(&qv.view([-1]), &mask);
+                    let action = qv.argmax(None, false).int64_value(&[]);
+                    (action as usize, qv.double_value(&[action]))
+                });
+                movimiento = decode_move(&actual_board, action).unwrap();
+                origen = format!("q {q:.4}");
             }
 
-            let movimiento_opt: Option<ChessMove> = moves.nth(action_index);
-            let actual_board = game_manager.board();
+            game_manager.do_move(movimiento);
+            game_manager.declare_draw();
+            let next_board = game_manager.board();
 
-            if let Some(movimiento) = movimiento_opt {
-                game_manager.do_move(movimiento);
-                game_manager.declare_draw();
-                let next_board = game_manager.board();
-
-                game_result = game_manager.result();
-
-                let buff_el = BufferElement {
-                    actual_state: actual_board.to_string(),
-                    action: action_index,
-                    reward: evaluate(&next_board, game_cont, game_result)
-                        * match actual_board.side_to_move() {
-                            Color::Black => -1.0,
-                            Color::White => 1.0,
-                        },
-                    next_state: next_board.to_string(),
-                    done: game_result.is_some(),
-                };
-
-                buffer.add(buff_el);
-
-                game_cont += 1;
-                force_random = false;
-            } 
-            else {
-                let buff_el = BufferElement {
-                    actual_state: actual_board.to_string(),
-                    action: action_index,
-                    reward: match actual_board.side_to_move() {
-                        Color::Black => 1.0,
-                        Color::White => -1.0,
-                    },
-                    next_state: actual_board.to_string(),
-                    done: true,
-                };
-
-                buffer.add(buff_el);
-                force_random = true;
+            game_result = game_manager.result();
+
+            if guardar_pgn {
+                let valor = evaluate(&next_board, game_cont, game_result);
+                comentarios.push(Some(format!("{origen}, eval {valor:.4}")));
+            }
+
+            let mover = actual_board.side_to_move();
+            let next_legal_actions = if game_result.is_some() { Vec::new() } else { game_manager.legal_actions() };
+
+            let buff_el = BufferElement {
+                actual_state,
+                action: encode_move(movimiento),
+                reward: evaluate_relative(&next_board, game_cont, game_result, mover),
+                next_state: game_manager.packed_state(),
+                next_legal_actions,
+                done: game_result.is_some(),
+                next_value,
+            };
+
+            for t in transiciones.push(buff_el, mover) {
+                buffer.add(t);
             }
 
+            game_cont += 1;
             total_cont += 1;
 
-            if total_cont > valor_minimo_entrenar && total_cont % frecuencia_entrenamiento == 0
+            // El calentamiento depende del contenido del buffer, asi un buffer recuperado no lo repite
+            if buffer.len() > valor_minimo_entrenar as usize
+                && total_cont % frecuencia_entrenamiento == 0
             {
-                let samples = buffer.get_samples(samples_size);
+                let samples = buffer.get_samples(samples_size, &mut rng);
+                let weights_tensor = Tensor::from_slice(samples.weights.as_slice()).to_device(device);
 
-                let mut actual_states: Vec<Tensor> = Vec::new();
-                let mut next_states: Vec<Tensor> = Vec::new();
                 let mut rewards: Vec<f32> = Vec::new();
                 let mut dones: Vec<bool> = Vec::new();
                 let mut actions: Vec<i32> = Vec::new();
-
-                for sample in samples {
-                    actual_states.push(board_to_tensor(
-                        &Board::from_str(&sample.actual_state).unwrap(),
-                    ));
-                    next_states.push(board_to_tensor(
-                        &Board::from_str(&sample.next_state).unwrap(),
-                    ));
+                let mut actual_states: Vec<&PackedState> = Vec::new();
+                let mut next_states: Vec<&PackedState> = Vec::new();
+                let mut next_legal_actions: Vec<&[u16]> = Vec::new();
+                let mut next_values: Vec<f32> = Vec::new();
+                let mut pesos_mcts: Vec<f32> = Vec::new();
+
+                for sample in &samples.elements {
+                    actual_states.push(&sample.actual_state);
+                    next_states.push(&sample.next_state);
+                    next_legal_actions.push(sample.next_legal_actions.as_slice());
                     rewards.push(sample.reward);
                     dones.push(sample.done);
                     actions.push(sample.action as i32);
+                    // Las transiciones sin valor MCTS (exploracion, argmax, PGN) usan solo la red target
+                    next_values.push(sample.next_value.unwrap_or(0.0));
+                    pesos_mcts.push(if sample.next_value.is_some() { config.mcts.bootstrap_weight as f32 } else { 0.0 });
                 }
 
-                let actual_states_tensor = Tensor::stack(actual_states.as_slice(), 0);
-                let next_states_tensor = Tensor::stack(next_states.as_slice(), 0);
-                let rewards_tensor = Tensor::from_slice(rewards.as_slice()).to_device(tch::Device::Mps);
-                let dones_tensor = Tensor::from_slice(dones.as_slice()).to(tch::Device::Mps);
+                let actual_states_tensor = batch_to_tensor(&actual_states, encoding).to_device(device);
+                let next_states_tensor = batch_to_tensor(&next_states, encoding).to_device(device);
+                let next_mask = legal_mask_batch(&next_legal_actions).to_device(device);
+                let rewards_tensor = Tensor::from_slice(rewards.as_slice()).to_device(device);
+                let dones_tensor = Tensor::from_slice(dones.as_slice()).to_device(device);
+                let next_values_tensor = Tensor::from_slice(next_values.as_slice()).to_device(device);
+                let pesos_mcts_tensor = Tensor::from_slice(pesos_mcts.as_slice()).to_device(device);
 
                 let mut td_target: Tensor =
                     Tensor::zeros(samples_size as i64, tch::kind::DOUBLE_CPU);
                 no_grad(|| {
-                    let (target_max, _) = target_network
-                        .forward(&next_states_tensor)
-                        .max_dim(1, false);
-
-                    td_target = (rewards_tensor + (gamma as f64 * target_max * dones_tensor.logical_not())).to(tch::Device::Mps);
+                    let target_q = target_network.forward_t(&next_states_tensor, false);
+
+                    let target_max = if config.double_dqn {
+                        // Double DQN: la red online elige la accion y la target la evalua
+                        let online_q = mask_illegal(&q_network.forward_t(&next_states_tensor, false), &next_mask);
+                        let best_actions = online_q.argmax(1, true);
+                        target_q.gather(1, &best_actions, false).squeeze_dim(1)
+                    }
+                    else {
+                        let (target_max, _) = mask_illegal(&target_q, &next_mask).max_dim(1, false);
+                        target_max
+                    };
+                    // Valor de s' para el bando que mueve en s', mezclado con el de MCTS si lo hay
+                    let target_max = &target_max + &pesos_mcts_tensor * (&next_values_tensor - &target_max);
+                    // Sin movimientos legales el max es -inf: los estados terminales no se usan
+                    let target_max = target_max.masked_fill(&dones_tensor, 0.0);
+
+                    // En negamax s' es turno del rival: su mejor valor es el peor para nosotros.
+                    // Con n pasos el estado de arranque es del rival solo si n es impar.
+                    let bootstrap = match config.target_mode {
+                        TargetMode::Negamax if config.n_step % 2 == 1 => -target_max,
+                        _ => target_max,
+                    };
+
+                    td_target = rewards_tensor + gamma_n * bootstrap;
                 });
 
-                let q_values: Tensor = q_network.forward(&actual_states_tensor).to(tch::Device::Mps);
+                let q_values: Tensor = q_network.forward_t(&actual_states_tensor, true);
                 let actions_tensor =
-                    Tensor::from_slice(actions.as_slice()).to_kind(tch::Kind::Int64).to_device(tch::Device::Mps);
+                    Tensor::from_slice(actions.as_slice()).to_kind(tch::Kind::Int64).to_device(device);
 
                 let q_values_action = q_values.gather(1, &actions_tensor.unsqueeze(-1), false);
                 //let loss = td_target.huber_loss(&q_values_action.squeeze_dim(1), tch::Reduction::Mean, 1.0);
                 //let loss = q_values_action.squeeze_dim(1).mse_loss(&td_target, tch::Reduction::Mean);
-                let loss = q_values_action.squeeze_dim(1).smooth_l1_loss(&td_target, tch::Reduction::Mean, 1.0);
+                let q_values_action = q_values_action.squeeze_dim(1);
+                let loss = (q_values_action.smooth_l1_loss(&td_target, tch::Reduction::None, 1.0) * &weights_tensor)
+                    .mean(tch::Kind::Float);
                 let loss_value = loss.double_value(&[]);
 
                 //optimizador.zero_grad();
@@ -187,6 +306,9 @@ pub fn dqn_train() {
                 
                 //optimizador.backward_step_clip(&loss, 10.0);
 
+                let td_errors = (&td_target - &q_values_action).detach().to_device(tch::Device::Cpu);
+                buffer.update_priorities(&samples.indices, &Vec::<f32>::try_from(&td_errors).unwrap());
+
                 if total_cont % 1000 == 0 {
                     let td_target_mean = td_target.to_device(tch::Device::Cpu).mean(tch::Kind::Double).double_value(&[]);
                     let q_values_action_mean =
@@ -200,16 +322,17 @@ pub fn dqn_train() {
                         steps_secs,
                     );
 
-                    write_to_csv(
-                        game_step,
-                        total_cont,
-                        td_target_mean,
-                        q_values_action_mean,
-                        loss_value,
+                    let registro = Registro {
+                        game: game_step,
+                        step: total_cont,
+                        td_target: td_target_mean,
+                        q_values_action: q_values_action_mean,
+                        loss: loss_value,
                         epsilon,
                         steps_secs,
-                        first_log,
-                    );
+                        double_dqn: config.double_dqn,
+                    };
+                    write_to_csv(&registro, first_log);
                     first_log = false;
                 }
             }
@@ -217,6 +340,10 @@ pub fn dqn_train() {
             if total_cont % target_network_update_freq == 0 {
                 _ = vs_target_network.copy(&vs_q_network).unwrap();
                 vs_q_network.save(ruta_nn).unwrap();
+
+                if config.replay.save_buffer {
+                    save_buffer(buffer.as_ref(), buffer_path);
+                }
             }
         }
 
@@ -232,7 +359,107 @@ pub fn dqn_train() {
             "Exploration: {random_moves} - Exploitation: {}",
             game_cont - random_moves
         );
-        //println!("{}", game_manager.get_historic());
+
+        if let (Some(curriculum), Some(fuerte)) = (curriculum.as_mut(), fuerte) {
+            let etapa = curriculum.stage_name();
+            let mate_rate = curriculum.mate_rate();
+            if curriculum.record(fuerte, game_result) {
+                write_stage_to_csv(game_step, etapa, curriculum.stage_name(), mate_rate);
+            }
+            _ = execute!(io::stdout(), MoveTo(0, 17));
+            println!(
+                "Curriculum: {} - mates: {:.2} ({} partidas)",
+                curriculum.stage_name(),
+                curriculum.mate_rate(),
+                curriculum.state().mates.len()
+            );
+        }
+
+        if guardar_pgn {
+            let headers = PgnHeaders { round: (game_step + 1).to_string(), ..PgnHeaders::new("Self-play", ruta_nn, ruta_nn) };
+            if let Err(e) = append_pgn(&config.pgn.path, &game_to_pgn(&game_manager, &headers, &comentarios)) {
+                println!("No se pudo guardar la partida en {}: {e}", config.pgn.path);
+            }
+        }
+
+        // Los checkpoints se escriben entre partidas para poder reanudar exactamente
+        let every_games = config.checkpoint.every_games;
+        // (el de la ultima partida se escribe al final del entrenamiento)
+        if every_games > 0 && (game_step + 1) % every_games == 0 && game_step + 1 < num_games {
+            let estado = TrainerState {
+                next_game: game_step + 1,
+                total_cont,
+                first_log,
+                rng: RngState::from_rng(&rng),
+                curriculum: curriculum.as_ref().map(|c| c.state().clone()).unwrap_or_default(),
+            };
+            write_checkpoint(&checkpoint, config, &estado, &vs_q_network, &vs_target_network, &optimizador, buffer.as_ref());
+        }
+    }
+
+    vs_q_network.save(ruta_nn).unwrap();
+
+    if config.replay.save_buffer {
+        save_buffer(buffer.as_ref(), buffer_path);
+    }
+
+    let estado = TrainerState {
+        next_game: num_games.max(start_game),
+        total_cont,
+        first_log,
+        rng: RngState::from_rng(&rng),
+        curriculum: curriculum.as_ref().map(|c| c.state().clone()).unwrap_or_default(),
+    };
+    write_checkpoint(&checkpoint, config, &estado, &vs_q_network, &vs_target_network, &optimizador, buffer.as_ref());
+}
+
+fn write_checkpoint(
+    checkpoint: &Checkpoint,
+    config: &TrainConfig,
+    estado: &TrainerState,
+    vs_q_network: &nn::VarStore,
+    vs_target_network: &nn::VarStore,
+    optimizador: &Adam,
+    buffer: &dyn Replay,
+) {
+    if let Err(e) = checkpoint.save(config, estado, vs_q_network, vs_target_network, optimizador, buffer) {
+        println!("No se pudo guardar el checkpoint en {}: {e}", checkpoint.dir().display());
+    }
+}
+
+pub fn create_buffer(config: &TrainConfig) -> Box<dyn Replay> {
+    let ruta = Path::new(&config.replay.buffer_path);
+
+    if config.replay.resume_buffer && ruta.exists() {
+        match load_buffer(ruta, config) {
+            Ok(buffer) => {
+                println!("Replay buffer recuperado: {} transiciones", buffer.len());
+                return buffer;
+            }
+            Err(e) => println!("No se pudo cargar {}: {e}", ruta.display()),
+        }
+    }
+
+    if config.replay.prioritized {
+        Box::new(PrioritizedReplayBuffer::new(config.buffer_capacity, &config.replay))
+    }
+    else {
+        Box::new(ReplayBuffer::new(config.buffer_capacity))
+    }
+}
+
+fn load_buffer(ruta: &Path, config: &TrainConfig) -> io::Result<Box<dyn Replay>> {
+    if config.replay.prioritized {
+        PrioritizedReplayBuffer::load(ruta, &config.replay).map(|b| Box::new(b) as Box<dyn Replay>)
+    }
+    else {
+        ReplayBuffer::load(ruta).map(|b| Box::new(b) as Box<dyn Replay>)
+    }
+}
+
+pub fn save_buffer(buffer: &dyn Replay, ruta: &Path) {
+    if let Err(e) = buffer.save(ruta) {
+        println!("No se pudo guardar {}: {e}", ruta.display());
     }
 }
 
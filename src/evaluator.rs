@@ -1,4 +1,7 @@
-use chess::{Board, Color, File, MoveGen, Piece, Rank, Square, ChessMove, GameResult};
+use chess::{
+    get_adjacent_files, get_bishop_moves, get_file, get_king_moves, get_knight_moves, get_pawn_attacks, get_rook_moves, BitBoard,
+    Board, ChessMove, Color, File, GameResult, MoveGen, Piece, Rank, Square, ALL_FILES, ALL_PIECES, EMPTY,
+};
 
 const PAWN_VALUE: i32 = 100;
 const KNIGHT_VALUE: i32 = 320;
@@ -9,6 +12,54 @@ const KING_VALUE: i32 = 5000;
 
 pub const MAX_REWARD: f32 = 10000.0;
 
+// Fase de juego: 24 con todo el material, 0 cuando solo quedan reyes y peones
+const PHASE_WEIGHT: [i32; 6] = [0, 1, 1, 2, 4, 0];
+const MAX_PHASE: i32 = 24;
+
+// Estructura de peones
+const DOUBLED_PAWN: i32 = 15;
+const ISOLATED_PAWN: i32 = 15;
+const BACKWARD_PAWN: i32 = 10;
+const CHAINED_PAWN: i32 = 10;
+// Bonus del peon pasado segun su fila relativa, en medio juego y en el final
+const PASSED_PAWN_MIDDLEGAME: [i32; 8] = [0, 5, 10, 15, 25, 40, 60, 0];
+const PASSED_PAWN_ENDGAME: [i32; 8] = [0, 10, 20, 35, 60, 100, 150, 0];
+
+// Seguridad del rey
+const SHELTER_CLOSE: i32 = 10;
+const SHELTER_FAR: i32 = 5;
+const SHELTER_MISSING: i32 = 10;
+const OPEN_FILE_NEAR_KING: i32 = 15;
+// Penalizacion por cada casilla de la zona del rey atacada, segun la pieza atacante
+const KING_ATTACK_WEIGHT: [i32; 6] = [0, 20, 20, 40, 80, 0];
+
+// Tablas por fila y columna desde el punto de vista de cada bando (fila 0 = primera fila propia)
+const KING_MIDDLEGAME: [[i32; 8]; 8] = [
+    [20, 30, 10, 0, 0, 10, 30, 20],
+    [20, 20, 0, 0, 0, 0, 20, 20],
+    [-10, -20, -20, -20, -20, -20, -20, -10],
+    [-20, -30, -30, -40, -40, -30, -30, -20],
+    [-30, -40, -40, -50, -50, -40, -40, -30],
+    [-30, -40, -40, -50, -50, -40, -40, -30],
+    [-30, -40, -40, -50, -50, -40, -40, -30],
+    [-30, -40, -40, -50, -50, -40, -40, -30],
+];
+
+const PAWN_MIDDLEGAME: [[i32; 8]; 8] = [
+    [0, 0, 0, 0, 0, 0, 0, 0],
+    [5, 10, 10, -20, -20, 10, 10, 5],
+    [5, -5, -10, 0, 0, -10, -5, 5],
+    [0, 0, 0, 20, 20, 0, 0, 0],
+    [5, 5, 10, 25, 25, 10, 5, 5],
+    [10, 10, 20, 30, 30, 20, 10, 10],
+    [50, 50, 50, 50, 50, 50, 50, 50],
+    [0, 0, 0, 0, 0, 0, 0, 0],
+];
+
+// En el final solo importa lo avanzado que esta el peon
+const PAWN_ENDGAME: [i32; 8] = [0, 10, 10, 20, 30, 50, 80, 0];
+
+// Rey en el final: cuanto mas centrado mejor
 const KING_PROXIMITY_CENTER: [[i32; 8]; 8] = [
     [-10, -8, -6, -4, -4, -6, -8, -10],
     [-8, -4,  0,  2,  2,  0, -4, -8],
@@ -35,7 +86,9 @@ pub fn evaluate(board: &Board, num_moves: u32, game_result: Option<GameResult>)
     - Piezas amenazadas
     - Piezas defendidas
     - Apertura (10 primeros movimientos) Control de centro
-    - Finales (num piezas <= 15) Rey en el centro
+    - Tablas de rey y peones interpoladas por fase (rey refugiado en el medio juego, centrado en el final)
+    - Estructura de peones: doblados, aislados, retrasados, encadenados y pasados
+    - Seguridad del rey: escudo de peones, columnas abiertas y ataques sobre su zona
         */
 
     let res = match game_result {
@@ -46,8 +99,12 @@ pub fn evaluate(board: &Board, num_moves: u32, game_result: Option<GameResult>)
             let pieces = get_pieces(board);
             let legal_moves = MoveGen::new_legal(board).collect::<Vec<ChessMove>>();
 
+            let fase = game_phase(board);
+
             let c_pieces = count_pieces(&pieces);
-            let king_position = king_position(board, &pieces);
+            let piece_squares = piece_square_tables(board, fase);
+            let pawns = pawn_structure(board, fase);
+            let king_safety = king_safety(board, fase);
             let threats = count_threats(board, &pieces, &legal_moves);
             let available_squares = count_available_squares(board, &pieces);
             let opening = if num_moves <= 20 {
@@ -57,7 +114,7 @@ pub fn evaluate(board: &Board, num_moves: u32, game_result: Option<GameResult>)
                 0
             };
             
-            (c_pieces + opening + king_position + threats + available_squares) as f32
+            (c_pieces + opening + piece_squares + pawns + king_safety + threats + available_squares) as f32
         }  
     };
 
@@ -65,6 +122,15 @@ pub fn evaluate(board: &Board, num_moves: u32, game_result: Option<GameResult>)
 
 }
 
+// Evaluacion desde el punto de vista de `color` (positiva si le favorece)
+pub fn evaluate_relative(board: &Board, num_moves: u32, game_result: Option<GameResult>, color: Color) -> f32 {
+    evaluate(board, num_moves, game_result)
+        * match color {
+            Color::Black => -1.0,
+            Color::White => 1.0,
+        }
+}
+
 fn opening(board: &Board, pieces: &[PieceComplete], moves: &[ChessMove]) -> i32 {
     let mut res = 0;
 
@@ -269,37 +335,148 @@ fn count_available_squares(board: &Board, pieces: &[PieceComplete]) -> i32 {
     res
 }
 
-fn king_position(board: &Board, pieces: &[PieceComplete]) -> i32 {
+fn game_phase(board: &Board) -> i32 {
+    let fase: i32 = ALL_PIECES
+        .iter()
+        .map(|p| board.pieces(*p).popcnt() as i32 * PHASE_WEIGHT[p.to_index()])
+        .sum();
+
+    fase.min(MAX_PHASE)
+}
+
+// Mezcla un valor de medio juego y otro de final segun la fase
+fn interpolate(middlegame: i32, endgame: i32, fase: i32) -> i32 {
+    (middlegame * fase + endgame * (MAX_PHASE - fase)) / MAX_PHASE
+}
+
+fn sign(color: Color) -> i32 {
+    match color {
+        Color::Black => -1,
+        Color::White => 1,
+    }
+}
+
+// Fila vista desde el bando (0 = su primera fila)
+fn relative_rank(square: Square, color: Color) -> usize {
+    match color {
+        Color::White => square.get_rank().to_index(),
+        Color::Black => 7 - square.get_rank().to_index(),
+    }
+}
+
+fn piece_square_tables(board: &Board, fase: i32) -> i32 {
     let mut res = 0;
 
-    // Finales
-    if pieces.len() <= 20 {
-        let king_square = board.king_square(Color::Black);
-        let king_rank = king_square.get_rank().to_index();
-        let king_file = king_square.get_file().to_index();
+    for color in [Color::White, Color::Black] {
+        let king_square = board.king_square(color);
+        let fila = relative_rank(king_square, color);
+        let columna = king_square.get_file().to_index();
+        res += sign(color) * interpolate(KING_MIDDLEGAME[fila][columna], KING_PROXIMITY_CENTER[fila][columna], fase);
 
-        res += -KING_PROXIMITY_CENTER[king_rank][king_file];
+        for square in *board.pieces(Piece::Pawn) & *board.color_combined(color) {
+            let fila = relative_rank(square, color);
+            let columna = square.get_file().to_index();
+            res += sign(color) * interpolate(PAWN_MIDDLEGAME[fila][columna], PAWN_ENDGAME[fila], fase);
+        }
+    }
 
-        let king_square = board.king_square(Color::White);
-        let king_rank = king_square.get_rank().to_index();
-        let king_file = king_square.get_file().to_index();
+    res
+}
 
-        res += KING_PROXIMITY_CENTER[king_rank][king_file];
-    } 
-    else {
-        let king_square = board.king_square(Color::Black);
-        let king_rank = king_square.get_rank();
+fn pawn_structure(board: &Board, fase: i32) -> i32 {
+    let mut res = 0;
 
-        if king_rank == Rank::Eighth {
-            res -= 50;
+    for color in [Color::White, Color::Black] {
+        let propios = *board.pieces(Piece::Pawn) & *board.color_combined(color);
+        let rivales = *board.pieces(Piece::Pawn) & *board.color_combined(!color);
+        let mut valor = 0;
+
+        for file in ALL_FILES {
+            let en_columna = (propios & get_file(file)).popcnt() as i32;
+            if en_columna > 1 {
+                valor -= DOUBLED_PAWN * (en_columna - 1);
+            }
         }
-        
-        let king_square = board.king_square(Color::White);
-        let king_rank = king_square.get_rank();
-        
-        if king_rank == Rank::First {
-            res += 50;
+
+        for square in propios {
+            let fila = relative_rank(square, color);
+            let vecinos = propios & get_adjacent_files(square.get_file());
+
+            if vecinos == EMPTY {
+                valor -= ISOLATED_PAWN;
+            }
+            // Retrasado: ningun peon vecino puede apoyarlo y un peon rival controla su casilla de avance
+            else if vecinos.into_iter().all(|v| relative_rank(v, color) > fila)
+                && square.forward(color).is_some_and(|avance| get_pawn_attacks(avance, color, rivales) != EMPTY)
+            {
+                valor -= BACKWARD_PAWN;
+            }
+
+            // Encadenado: defendido por un peon propio
+            if get_pawn_attacks(square, !color, propios) != EMPTY {
+                valor += CHAINED_PAWN;
+            }
+
+            // Pasado: sin peones rivales por delante en su columna ni en las adyacentes
+            let bloqueo = rivales & (get_file(square.get_file()) | get_adjacent_files(square.get_file()));
+            if bloqueo.into_iter().all(|r| relative_rank(r, color) <= fila) {
+                valor += interpolate(PASSED_PAWN_MIDDLEGAME[fila], PASSED_PAWN_ENDGAME[fila], fase);
+            }
         }
+
+        res += sign(color) * valor;
+    }
+
+    res
+}
+
+// Escudo de peones y ataques sobre la zona del rey; pierde peso a medida que se cambian piezas
+fn king_safety(board: &Board, fase: i32) -> i32 {
+    let mut res = 0;
+    let ocupadas = *board.combined();
+    let peones = *board.pieces(Piece::Pawn);
+
+    for color in [Color::White, Color::Black] {
+        let rey = board.king_square(color);
+        let fila_rey = relative_rank(rey, color);
+        let propios = peones & *board.color_combined(color);
+        let columna_rey = rey.get_file().to_index();
+        let mut refugio = 0;
+
+        for columna in columna_rey.saturating_sub(1)..=(columna_rey + 1).min(7) {
+            let mascara = get_file(File::from_index(columna));
+
+            let escudo = (propios & mascara)
+                .into_iter()
+                .map(|p| relative_rank(p, color))
+                .filter(|fila| *fila > fila_rey)
+                .min();
+            refugio += match escudo.map(|fila| fila - fila_rey) {
+                Some(1) => SHELTER_CLOSE,
+                Some(2) => SHELTER_FAR,
+                _ => -SHELTER_MISSING,
+            };
+
+            if peones & mascara == EMPTY {
+                refugio -= OPEN_FILE_NEAR_KING;
+            }
+        }
+
+        let zona = get_king_moves(rey) | BitBoard::from_square(rey);
+        let mut ataque = 0;
+        for pieza in [Piece::Knight, Piece::Bishop, Piece::Rook, Piece::Queen] {
+            for square in *board.pieces(pieza) & *board.color_combined(!color) {
+                let ataques = match pieza {
+                    Piece::Knight => get_knight_moves(square),
+                    Piece::Bishop => get_bishop_moves(square, ocupadas),
+                    Piece::Rook => get_rook_moves(square, ocupadas),
+                    _ => get_bishop_moves(square, ocupadas) | get_rook_moves(square, ocupadas),
+                };
+                ataque += (ataques & zona).popcnt() as i32 * KING_ATTACK_WEIGHT[pieza.to_index()];
+            }
+        }
+
+        res += sign(color) * interpolate(refugio - ataque, 0, fase);
     }
 
     res
@@ -352,3 +529,110 @@ fn piece_value(piece: &PieceComplete) -> i32 {
         Color::White => v,
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use super::*;
+
+    fn board(fen: &str) -> Board {
+        Board::from_str(fen).unwrap()
+    }
+
+    // Misma posicion con los colores cambiados: filas invertidas, mayusculas por minusculas y el otro bando al turno
+    fn mirror(fen: &str) -> String {
+        let campos: Vec<&str> = fen.split_whitespace().collect();
+        let cambiar = |c: char| if c.is_ascii_uppercase() { c.to_ascii_lowercase() } else { c.to_ascii_uppercase() };
+
+        let colocacion: Vec<String> = campos[0].split('/').rev().map(|fila| fila.chars().map(cambiar).collect()).collect();
+        let turno = if campos[1] == "w" { "b" } else { "w" };
+        let enroques: String = match campos[2] {
+            "-" => "-".to_owned(),
+            enroques => "KQkq".chars().filter(|c| enroques.contains(cambiar(*c))).collect(),
+        };
+        let al_paso = match campos[3] {
+            "-" => "-".to_owned(),
+            casilla => format!("{}{}", &casilla[..1], if &casilla[1..] == "6" { 3 } else { 6 }),
+        };
+
+        format!("{} {turno} {enroques} {al_paso} 0 1", colocacion.join("/"))
+    }
+
+    #[test]
+    fn evaluation_is_colour_symmetric() {
+        for fen in [
+            "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1",
+            "r1bqkb1r/pppp1ppp/2n2n2/4p3/2B1P3/5N2/PPPP1PPP/RNBQK2R w KQkq - 4 4",
+            "rnbqkbnr/ppp1p1pp/8/3pPp2/8/8/PPPP1PPP/RNBQKBNR w KQkq f6 0 3",
+            "r2q1rk1/pp2bppp/2n1pn2/3p4/3P4/2NBPN2/PP3PPP/R2Q1RK1 b - - 0 9",
+            "8/5pk1/6p1/3P4/2K5/8/6P1/8 w - - 0 40",
+            "4k3/8/8/8/8/8/8/R3K3 b - - 0 1",
+        ] {
+            for num_moves in [4, 40] {
+                let original = evaluate(&board(fen), num_moves, None);
+                let espejo = evaluate(&board(&mirror(fen)), num_moves, None);
+                assert!((original + espejo).abs() < 1e-6, "{fen} ({num_moves}): {original} vs {espejo}");
+            }
+        }
+
+        assert_eq!(evaluate(&Board::default(), 0, Some(GameResult::WhiteCheckmates)), 1.0);
+        assert_eq!(evaluate(&Board::default(), 0, Some(GameResult::WhiteResigns)), -1.0);
+    }
+
+    #[test]
+    fn weak_pawns_score_lower() {
+        // Peones negros en c7-f7 para que ningun peon blanco sea pasado
+        let estructura = |blancas: &str| {
+            let board = board(&format!("4k3/2pppp2/8/8/{blancas}/4K3 w - - 0 1"));
+            pawn_structure(&board, game_phase(&board))
+        };
+
+        let sanos = estructura("8/8/3PP3");
+        assert!(estructura("8/8/2P1P3") < sanos, "aislados");
+        assert!(estructura("3P4/8/3P4") < sanos, "doblados");
+    }
+
+    #[test]
+    fn passed_pawn_gains_as_it_advances() {
+        let mut anterior = i32::MIN;
+        for fila in 2..=7 {
+            let mut filas = ["8"; 8];
+            filas[0] = "7k";
+            filas[7] = "K7";
+            filas[8 - fila] = "3P4";
+            let board = board(&format!("{} w - - 0 1", filas.join("/")));
+
+            let valor = pawn_structure(&board, game_phase(&board));
+            assert!(valor > anterior, "fila {fila}: {valor} <= {anterior}");
+            anterior = valor;
+        }
+
+        // Con material en el tablero tambien cuenta, aunque menos
+        let medio_juego = board("r2qk2r/8/8/3P4/8/8/8/R2QK2R w - - 0 1");
+        assert!(pawn_structure(&medio_juego, game_phase(&medio_juego)) > 0);
+    }
+
+    #[test]
+    fn missing_king_shelter_is_penalised() {
+        let seguridad = |fen: &str| {
+            let board = board(fen);
+            king_safety(&board, game_phase(&board))
+        };
+
+        let intacto = seguridad("r2q1rk1/ppp2ppp/2n5/8/8/2N5/PPP2PPP/R2Q1RK1 w - - 0 1");
+        let avanzado = seguridad("r2q1rk1/ppp2ppp/2n5/8/8/2N3P1/PPP2P1P/R2Q1RK1 w - - 0 1");
+        let sin_peon = seguridad("r2q1rk1/ppp2ppp/2n5/8/8/2N5/PPP2P1P/R2Q1RK1 w - - 0 1");
+        let columna_abierta = seguridad("r2q1rk1/ppp2p1p/2n5/8/8/2N5/PPP2P1P/R2Q1RK1 w - - 0 1");
+
+        assert!(intacto > avanzado, "{intacto} <= {avanzado}");
+        assert!(avanzado > sin_peon, "{avanzado} <= {sin_peon}");
+        // Sin los dos peones de g la columna queda abierta para los dos reyes: se compensa
+        assert_eq!(columna_abierta, 0);
+
+        // En el final el refugio ya no importa
+        let final_intacto = seguridad("6k1/5ppp/8/8/8/8/5PPP/6K1 w - - 0 1");
+        let final_sin_peon = seguridad("6k1/5ppp/8/8/8/8/5P1P/6K1 w - - 0 1");
+        assert_eq!(final_intacto, final_sin_peon);
+    }
+}
@@ -1,67 +1,218 @@
-use chess::{Board, ChessMove, Color, Game, GameResult, MoveGen};
+use std::str::FromStr;
+
+use chess::{Board, ChessMove, Color, Game, GameResult, MoveGen, Piece};
+
+use crate::{move_encoding::encode_move, state_encoding::PackedState};
 
 pub struct GameManager {
     game: Game,
+    // Posicion de partida, para reproducir los movimientos (PGN)
+    inicial: Board,
+    // Contadores del FEN de partida, que Board no guarda
+    halfmove_inicial: u32,
+    fullmove_inicial: u32,
+    // Medias jugadas desde la ultima captura o movimiento de peon
+    halfmove_clock: u32,
+    // Hashes de las posiciones desde el ultimo movimiento irreversible
+    historial: Vec<u64>,
+    // Tablas por la regla de 50 contando las medias jugadas previas al FEN, que Game no conoce
+    tablas_50: bool,
 }
 
 impl GameManager {
     pub fn new() -> GameManager {
-        GameManager { game: Game::new() }
+        GameManager::from_board(Board::default(), 0)
+    }
+
+    // FEN completo o solo sus cuatro primeros campos (EPD). Board no guarda los contadores
+    // de la regla de 50 movimientos ni el numero de jugada, asi que se toman del quinto y sexto campo.
+    pub fn from_fen(fen: &str) -> Result<GameManager, String> {
+        let campos: Vec<&str> = fen.split_whitespace().collect();
+        let colocacion = campos.first().copied().unwrap_or_default();
+
+        // chess no comprueba los reyes antes de generar movimientos
+        if colocacion.matches('K').count() != 1 || colocacion.matches('k').count() != 1 {
+            return Err(format!("FEN invalido: {fen}"));
+        }
+
+        let board = Board::from_str(fen).map_err(|_| format!("FEN invalido: {fen}"))?;
+        let halfmove_clock = campos.get(4).and_then(|h| h.parse().ok()).unwrap_or(0);
+        let fullmove = campos.get(5).and_then(|f| f.parse().ok()).filter(|f| *f > 0).unwrap_or(1);
+
+        let mut game_manager = GameManager::from_board(board, halfmove_clock);
+        game_manager.fullmove_inicial = fullmove;
+        Ok(game_manager)
+    }
+
+    pub fn from_board(board: Board, halfmove_clock: u32) -> GameManager {
+        let game = Game::new_with_board(board);
+        let historial = vec![board.get_hash()];
+
+        GameManager {
+            game,
+            inicial: board,
+            halfmove_inicial: halfmove_clock,
+            fullmove_inicial: 1,
+            halfmove_clock,
+            historial,
+            tablas_50: false,
+        }
     }
 
     pub fn do_move(&mut self, chess_move: ChessMove) -> (bool, Option<GameResult>) {
+        if self.tablas_50 {
+            return (false, self.result());
+        }
+
+        let board = self.game.current_position();
+        let irreversible = board.piece_on(chess_move.get_source()) == Some(Piece::Pawn)
+            || board.piece_on(chess_move.get_dest()).is_some();
+
         let res = self.game.make_move(chess_move);
 
+        if res {
+            if irreversible {
+                self.halfmove_clock = 0;
+                self.historial.clear();
+            }
+            else {
+                self.halfmove_clock += 1;
+            }
+            self.historial.push(self.game.current_position().get_hash());
+        }
+
         (res, self.game.result())
     }
 
+    // Veces que la posicion actual ya habia aparecido
+    pub fn repetitions(&self) -> u32 {
+        let actual = self.game.current_position().get_hash();
+        self.historial.iter().filter(|h| **h == actual).count() as u32 - 1
+    }
+
+    // Posiciones anteriores que aun pueden repetirse (desde el ultimo movimiento irreversible)
+    pub fn previous_positions(&self) -> &[u64] {
+        &self.historial[..self.historial.len() - 1]
+    }
+
+    pub fn initial_board(&self) -> Board {
+        self.inicial
+    }
+
+    pub fn initial_fullmove(&self) -> u32 {
+        self.fullmove_inicial
+    }
+
+    // FEN de la posicion de partida con sus contadores (Board siempre escribe "0 1")
+    pub fn initial_fen(&self) -> String {
+        let fen = self.inicial.to_string();
+        let campos: Vec<&str> = fen.split_whitespace().take(4).collect();
+
+        format!("{} {} {}", campos.join(" "), self.halfmove_inicial, self.fullmove_inicial)
+    }
+
+    // Movimientos jugados desde la posicion inicial
+    pub fn moves(&self) -> Vec<ChessMove> {
+        self.game
+            .actions()
+            .iter()
+            .filter_map(|a| match a {
+                chess::Action::MakeMove(m) => Some(*m),
+                _ => None,
+            })
+            .collect()
+    }
+
+    pub fn num_moves(&self) -> u32 {
+        self.game.actions().iter().filter(|a| matches!(a, chess::Action::MakeMove(_))).count() as u32
+    }
+
+    pub fn packed_state(&self) -> PackedState {
+        PackedState::from_board(&self.board()).with_history(self.halfmove_clock, self.repetitions())
+    }
+
     pub fn get_moves(&self) -> MoveGen {
         MoveGen::new_legal(&self.game.current_position())
     }
 
+    pub fn legal_actions(&self) -> Vec<u16> {
+        self.get_moves().map(|m| encode_move(m) as u16).collect()
+    }
+
     pub fn side_to_move(&self) -> Color {
         self.game.side_to_move()
     }
 
     pub fn result(&self) -> Option<GameResult> {
-        self.game.result()
+        match self.game.result() {
+            None if self.tablas_50 => Some(GameResult::DrawDeclared),
+            resultado => resultado,
+        }
     }
 
     pub fn board(&self) -> Board {
         self.game.current_position()
     }
 
+    // Game solo cuenta la regla de 50 desde su posicion de partida: con un FEN a mitad de partida
+    // se declaran tablas tambien cuando el contador completo llega a 100 medias jugadas
     pub fn declare_draw(&mut self) -> bool {
-        self.game.declare_draw()
-    }
-
-    pub fn get_historic(&self) -> String {
-        let actions = self.game.actions();
-        let mut res: String = String::new();
-        let mut cont = 1;
-        
-        for ac in actions {
-            let s = match ac {
-                chess::Action::MakeMove(cm) => cm.to_string(),
-                chess::Action::OfferDraw(c) => {
-                    match c {
-                                    Color::Black => "Black Offer Draw",
-                                    Color::White => "White Offer Draw"
-                                }
-                }.to_owned(),
-                chess::Action::AcceptDraw => "Accept Draw".to_owned(),
-                chess::Action::DeclareDraw => "Declare Draw".to_owned(),
-                chess::Action::Resign(c) => {
-                    match c {
-                        Color::White => "White resign",
-                        Color::Black => "Black resign",
-                    }
-                }.to_owned(),
-            };
-            res.push_str(&format!("{}:{:?}, ", cont, s));
-            cont += 1;
+        if self.game.declare_draw() {
+            return true;
+        }
+
+        if self.result().is_none() && self.halfmove_clock >= 100 {
+            self.tablas_50 = true;
         }
+        self.tablas_50
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn movimiento(uci: &str) -> ChessMove {
+        ChessMove::from_str(uci).unwrap()
+    }
+
+    #[test]
+    fn from_fen_requires_one_king_per_side() {
+        assert!(GameManager::from_fen("8/8/8/8/8/8/8/4K3 w - - 0 1").is_err());
+        assert!(GameManager::from_fen("4k3/8/8/8/8/8/8/3KK3 w - - 0 1").is_err());
+        assert!(GameManager::from_fen("4k3/8/8/8/8/8/8/4K3 x - - 0 1").is_err());
+        assert!(GameManager::from_fen("").is_err());
+        assert!(GameManager::from_fen("4k3/8/8/8/8/8/8/4K3 w - - 0 1").is_ok());
+    }
+
+    #[test]
+    fn from_fen_reads_move_counters() {
+        let game_manager = GameManager::from_fen("4k3/8/8/8/8/8/8/R3K3 w - - 12 30").unwrap();
+        assert_eq!(game_manager.initial_fen(), "4k3/8/8/8/8/8/8/R3K3 w - - 12 30");
+        assert_eq!(game_manager.initial_fullmove(), 30);
+        assert_eq!(game_manager.packed_state(), PackedState::from_board(&game_manager.board()).with_history(12, 0));
+
+        // EPD sin contadores
+        let game_manager = GameManager::from_fen("4k3/8/8/8/8/8/8/R3K3 w - -").unwrap();
+        assert_eq!(game_manager.initial_fen(), "4k3/8/8/8/8/8/8/R3K3 w - - 0 1");
+    }
+
+    #[test]
+    fn fifty_move_rule_counts_fen_halfmoves() {
+        let mut game_manager = GameManager::from_fen("4k3/8/8/8/8/8/8/R3K3 w - - 98 80").unwrap();
+
+        game_manager.do_move(movimiento("a1a2"));
+        assert!(!game_manager.declare_draw());
+        assert_eq!(game_manager.result(), None);
+
+        game_manager.do_move(movimiento("e8d8"));
+        assert!(game_manager.declare_draw());
+        assert_eq!(game_manager.result(), Some(GameResult::DrawDeclared));
+        assert!(!game_manager.do_move(movimiento("a2a3")).0);
 
-        res
+        // Una captura o un movimiento de peon reinician el contador
+        let mut game_manager = GameManager::from_fen("4k3/8/8/8/8/8/4P3/R3K3 w - - 99 80").unwrap();
+        game_manager.do_move(movimiento("e2e3"));
+        assert!(!game_manager.declare_draw());
     }
 }
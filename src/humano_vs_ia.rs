@@ -1,31 +1,23 @@
-use std::{io::{self}, path::Path, process::exit};
+use std::io::{self};
 
 use chess::{Color, ChessMove};
 use crossterm::{execute, cursor::{MoveTo, Show, EnableBlinking}, style::Print, terminal};
-use tch::{nn::{self, Module}, no_grad, Tensor};
 
-use crate::{game_manager::GameManager, dqn_nn_model::DQNModelNN, board_controller::{display_board, board_to_tensor}, evaluator::evaluate};
+use crate::{
+    game_manager::GameManager,
+    board_controller::display_board,
+    evaluator::evaluate,
+    pgn::{append_pgn, game_to_pgn, PgnHeaders},
+    player::Player,
+};
 
-pub fn jugar(human_side: Color) {
-    let ruta_nn = "nn.pth";
-    let load_path = Path::new(ruta_nn);
-
-    let mut vs = nn::VarStore::new(tch::Device::Mps);
-    let q_network = DQNModelNN::new(&vs.root());
-
-    if load_path.exists() {
-        vs.load(load_path).unwrap();
-    }
-    else {
-        println!("No se encontro la red neuronal");
-        exit(1);
-    }
-   
+// La partida se anade al final de ruta_pgn con la evaluacion de cada movimiento como comentario
+pub fn jugar(human_side: Color, oponente: &mut dyn Player, ruta_pgn: &str, mut game_manager: GameManager) {
     _ = execute!(io::stdout(), Show, EnableBlinking);
 
-    let mut game_manager = GameManager::new();
     let mut game_result: Option<chess::GameResult> = game_manager.result();
     let mut moves_cont = 0;
+    let mut comentarios: Vec<Option<String>> = Vec::new();
 
     while game_manager.declare_draw() || game_result.is_none() {
         moves_cont += 1;
@@ -35,13 +27,30 @@ pub fn jugar(human_side: Color) {
             let valor = evaluate(&board, moves_cont, game_manager.result());
             _ = execute!(io::stdout(), MoveTo(0, 15));
             print!("Valor: {}", valor);
+            if let Some(valor_ia) = oponente.value(&game_manager) {
+                _ = execute!(io::stdout(), MoveTo(0, 16));
+                print!("Valor IA (blancas): {:.4}", valor_ia);
+            }
+            let jugados = game_manager.num_moves();
             human_make_move(&mut game_manager);
             let valor = evaluate(&game_manager.board(), moves_cont, game_manager.result());
             _ = execute!(io::stdout(), MoveTo(0, 15));
             print!("Valor: {}", valor);
+            if game_manager.num_moves() > jugados {
+                comentarios.push(Some(format!("eval {valor:.4}")));
+            }
         } 
         else {
-            ia_make_move(&mut game_manager, &q_network);
+            println!("Movimientos posibles: {:?}", game_manager.get_moves().len());
+            let movimiento = oponente.choose_move(&game_manager);
+            game_manager.do_move(movimiento);
+
+            let valor = evaluate(&game_manager.board(), moves_cont, game_manager.result());
+            let comentario = match oponente.value(&game_manager) {
+                Some(valor_ia) => format!("eval {valor:.4}, ia {valor_ia:.4}"),
+                None => format!("eval {valor:.4}"),
+            };
+            comentarios.push(Some(comentario));
         }
 
         game_result = game_manager.result();
@@ -50,42 +59,19 @@ pub fn jugar(human_side: Color) {
     _ = execute!(io::stdout(), MoveTo(0, 11), Print("Juego terminado\n"));
     _ = execute!(io::stdout(), MoveTo(0, 12), Print("Resultado: "));
     _ = execute!(io::stdout(), MoveTo(0, 13), Print(format!("{:?}\n", game_result.unwrap())));
+    let (blancas, negras) = match human_side {
+        Color::White => ("Humano".to_owned(), oponente.nombre()),
+        Color::Black => (oponente.nombre(), "Humano".to_owned()),
+    };
+    let pgn = game_to_pgn(&game_manager, &PgnHeaders::new("Humano vs Maquina", &blancas, &negras), &comentarios);
+    if let Err(e) = append_pgn(ruta_pgn, &pgn) {
+        _ = execute!(io::stdout(), MoveTo(0, 15), Print(format!("No se pudo guardar la partida en {ruta_pgn}: {e}")));
+    }
+
     _ = execute!(io::stdout(), MoveTo(0, 14), Print("Pulse ENTER para continuar..."));
     io::stdin().read_line(&mut String::new()).unwrap();
 }
 
-fn ia_make_move(game_manager: &mut GameManager, q_network: &DQNModelNN) {
-    let moves = game_manager.get_moves();
-    let moves_vec = moves.into_iter().collect::<Vec<ChessMove>>();
-
-    let mut filtered_output: Tensor = Default::default();
-    no_grad(|| {
-        let qv = q_network.forward(&board_to_tensor(&game_manager.board()));
-        //let softmax = qv.softmax(0, tch::Kind::Float);
-        //filtered_output = softmax.argsort(-1,true);
-        filtered_output = qv.argsort(-1,true);
-    });
-
-    let mut correct_index = false;
-    let mut i = 0;
-
-    println!("Movimientos posibles: {:?}", moves_vec.len());
-
-    while !correct_index {
-        let action_index = filtered_output.int64_value(&[0, i]) as usize;
-        let movimiento_opt = moves_vec.get(action_index);
-
-        if let Some(movimiento) = movimiento_opt {
-            let (move_is_good, _game_res_opt)  = game_manager.do_move(*movimiento);
-
-            if move_is_good {
-                correct_index = true;
-            }
-        }
-        i += 1;
-    }
-}
-
 fn human_make_move(game_manager: &mut GameManager) {
     let move_str = get_move_str();
     let chess_move_res = ChessMove::from_san(&game_manager.board(), &move_str.trim());
@@ -0,0 +1,97 @@
+use std::collections::HashMap;
+
+use chess::{Board, ChessMove, MoveGen, EMPTY};
+use clap::ValueEnum;
+use tch::{nn::{ModuleT, VarStore}, no_grad, Device};
+
+use crate::{
+    board_controller::board_to_tensor,
+    dqn_nn_model::DQNModelNN,
+    evaluator::MAX_REWARD,
+    move_encoding::{encode_move, legal_mask},
+    search::{ClassicalHooks, SearchHooks, MATE},
+    state_encoding::PackedState,
+};
+
+// Que parte de la busqueda alpha-beta se delega en la red
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum HybridMode {
+    // Los Q-values ordenan los movimientos tranquilos; las hojas usan evaluator::evaluate
+    Ordering,
+    // Las hojas se puntuan con el valor de la red; el orden es el clasico
+    Leaves,
+    Both,
+}
+
+// Los valores de la red estan en la escala de las recompensas: evaluate() / MAX_REWARD
+const ESCALA: f64 = MAX_REWARD as f64;
+// Lejos de las puntuaciones de mate y por debajo de la prioridad de las capturas
+const LIMITE: i32 = MATE / 2;
+// Entradas de la cache de evaluaciones; al llenarse se vacia entera
+const CACHE_SIZE: usize = 1 << 20;
+
+pub struct NetworkHooks {
+    _vs: VarStore,
+    q_network: DQNModelNN,
+    device: Device,
+    mode: HybridMode,
+    // Valor de la red por Board::get_hash: la quiescence visita muchas veces las mismas posiciones
+    cache: HashMap<u64, i32>,
+}
+
+impl NetworkHooks {
+    pub fn new(vs: VarStore, q_network: DQNModelNN, device: Device, mode: HybridMode) -> NetworkHooks {
+        NetworkHooks { _vs: vs, q_network, device, mode, cache: HashMap::new() }
+    }
+
+    // Dentro de la busqueda no hay historial: regla de 50 y repeticiones a 0
+    fn input(&self, board: &Board) -> tch::Tensor {
+        board_to_tensor(&PackedState::from_board(board), self.q_network.encoding()).to_device(self.device)
+    }
+}
+
+impl SearchHooks for NetworkHooks {
+    fn evaluate(&mut self, board: &Board, num_moves: u32, ply: u32) -> i32 {
+        if self.mode == HybridMode::Ordering {
+            return ClassicalHooks.evaluate(board, num_moves, ply);
+        }
+
+        // Sin cabeza dueling el valor es el maximo Q legal; sin movimientos es mate o ahogado, como en search
+        let legal_actions: Vec<u16> = MoveGen::new_legal(board).map(|m| encode_move(m) as u16).collect();
+        if legal_actions.is_empty() {
+            return if *board.checkers() != EMPTY { -MATE + ply as i32 } else { 0 };
+        }
+
+        let hash = board.get_hash();
+        if let Some(valor) = self.cache.get(&hash) {
+            return *valor;
+        }
+
+        let mask = legal_mask(&legal_actions);
+        let valor = no_grad(|| self.q_network.state_value(&self.input(board), &mask)).double_value(&[0]);
+        let valor = ((valor * ESCALA) as i32).clamp(-LIMITE, LIMITE);
+
+        if self.cache.len() >= CACHE_SIZE {
+            self.cache.clear();
+        }
+        self.cache.insert(hash, valor);
+
+        valor
+    }
+
+    fn order_moves(&mut self, board: &Board, moves: &[ChessMove]) -> Option<Vec<i32>> {
+        if self.mode == HybridMode::Leaves {
+            return None;
+        }
+
+        let qv = no_grad(|| self.q_network.forward_t(&self.input(board), false)).to_device(Device::Cpu);
+        let q_values = Vec::<f32>::try_from(qv.view([-1])).unwrap();
+
+        Some(
+            moves
+                .iter()
+                .map(|m| ((q_values[encode_move(*m)] as f64 * ESCALA) as i32).clamp(-LIMITE, LIMITE))
+                .collect(),
+        )
+    }
+}
@@ -6,9 +6,34 @@ mod replay_buffer;
 mod dqn_nn_model;
 mod csv_manager;
 mod humano_vs_ia;
+mod device;
+mod cli;
+mod analyze;
+mod model_match;
+mod train_config;
+mod move_encoding;
+mod sum_tree;
+mod prioritized_replay_buffer;
+mod n_step;
+mod state_encoding;
+mod binary_io;
+mod adam;
+mod checkpoint;
+mod player;
+mod arena;
+mod search;
+mod hybrid_search;
+mod mcts;
+mod pgn;
+mod pretrain;
+mod start_positions;
+mod curriculum;
+#[cfg(test)]
+mod test_utils;
 
-use std::io::{self, Write};
+use std::{io::{self, Write}, process::exit};
 
+use clap::Parser;
 use crossterm::{
     cursor::{EnableBlinking, Hide, MoveTo, MoveToNextLine, Show},
     event::{read, KeyCode},
@@ -17,7 +42,75 @@ use crossterm::{
     terminal::{self, Clear, ClearType},
 };
 
+use cli::{Cli, Comando};
+use game_manager::GameManager;
+use tch::Device;
+
 fn main() {
+    let cli = Cli::parse();
+    let device = device::select_device(cli.device.as_deref());
+
+    match cli.command {
+        None => menu_interactivo(device),
+        Some(Comando::Train(args)) => match args.to_config() {
+            Ok(config) => dqn_train::dqn_train(&config, device),
+            Err(e) => {
+                println!("{e}");
+                exit(1);
+            }
+        },
+        Some(Comando::Pretrain(args)) => match args.to_config() {
+            Ok(config) => pretrain::pretrain(&config, &args.pgn, args.mode, args.epochs, args.max_games, device),
+            Err(e) => {
+                println!("{e}");
+                exit(1);
+            }
+        },
+        Some(Comando::Play { side, model, opponent, pgn, fen, search }) => {
+            let game_manager = match fen.as_deref().map(GameManager::from_fen) {
+                Some(Ok(game_manager)) => game_manager,
+                Some(Err(e)) => {
+                    println!("{e}");
+                    exit(1);
+                }
+                None => GameManager::new(),
+            };
+
+            match player::create_player(opponent, &model, search.limits(), search.hybrid_mode, &search.mcts(), device) {
+                Ok(mut oponente) => humano_vs_ia::jugar(side.into(), oponente.as_mut(), &pgn, game_manager),
+                Err(e) => {
+                    println!("{e}");
+                    exit(1);
+                }
+            }
+        }
+        Some(Comando::Analyze { fen, model, top }) => analyze::analizar(fen.as_deref(), &model, top, device),
+        Some(Comando::Match { white, black, max_moves }) => model_match::enfrentar(&white, &black, max_moves, device),
+        Some(Comando::Arena { player_a, kind_a, player_b, kind_b, games, max_moves, openings, search }) => {
+            let mut jugadores = Vec::new();
+            for (kind, ruta) in [(kind_a, &player_a), (kind_b, &player_b)] {
+                match player::create_player(kind, ruta, search.limits(), search.hybrid_mode, &search.mcts(), device) {
+                    Ok(jugador) => jugadores.push(jugador),
+                    Err(e) => {
+                        println!("{e}");
+                        exit(1);
+                    }
+                }
+            }
+            let jugador_b = jugadores.pop().unwrap();
+            let jugador_a = jugadores.pop().unwrap();
+            arena::arena(jugador_a, jugador_b, games, max_moves, openings.as_deref())
+        }
+        Some(Comando::Export { model, output }) => {
+            if let Err(e) = dqn_nn_model::export_model(&model, &output, device) {
+                println!("No se pudo exportar la red: {e}");
+                exit(1);
+            }
+        }
+    }
+}
+
+fn menu_interactivo(device: Device) {
     _ = terminal::enable_raw_mode();
 
     let opciones = vec![
@@ -57,9 +150,9 @@ fn main() {
                     KeyCode::Enter => {
                         _ = execute!(io::stdout(), Clear(ClearType::All), MoveTo(0, 0));
                         match seleccionado {
-                            0 => { dqn_train::dqn_train() }
-                            1 => { humano_vs_ia::jugar(chess::Color::White) }
-                            2 => { humano_vs_ia::jugar(chess::Color::Black) }
+                            0 => { dqn_train::dqn_train(&train_config::TrainConfig::default(), device) }
+                            1 => { humano_vs_ia::jugar(chess::Color::White, model_match::cargar_jugador("nn.pth", device).as_mut(), "games.pgn", GameManager::new()) }
+                            2 => { humano_vs_ia::jugar(chess::Color::Black, model_match::cargar_jugador("nn.pth", device).as_mut(), "games.pgn", GameManager::new()) }
                             3 => { println!("No implementado") }
                             _ => {}
                         }
@@ -0,0 +1,182 @@
+use chess::{Board, ChessMove, MoveGen, EMPTY};
+use serde::{Deserialize, Serialize};
+use tch::{no_grad, Device};
+
+use crate::{
+    board_controller::board_to_tensor,
+    dqn_nn_model::DQNModelNN,
+    game_manager::GameManager,
+    move_encoding::encode_move,
+    state_encoding::PackedState,
+};
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct MctsConfig {
+    pub simulations: u32,
+    // Peso de la exploracion en PUCT
+    pub c_puct: f64,
+    // Temperatura del softmax de los Q-values legales que da los priors.
+    // Los Q-values estan en la escala de las recompensas (un peon ~ 0.01).
+    pub temperature: f64,
+    // En self-play los movimientos de explotacion se eligen con MCTS en lugar del argmax
+    pub self_play: bool,
+    // Peso del valor MCTS de s' en el bootstrap del TD target (0: solo la red target, 1: solo MCTS)
+    pub bootstrap_weight: f64,
+}
+
+impl Default for MctsConfig {
+    fn default() -> Self {
+        MctsConfig {
+            simulations: 200,
+            c_puct: 1.5,
+            temperature: 0.01,
+            self_play: false,
+            bootstrap_weight: 0.0,
+        }
+    }
+}
+
+impl MctsConfig {
+    pub fn validate(&self) -> Vec<String> {
+        let mut errores: Vec<String> = Vec::new();
+
+        if self.simulations == 0 {
+            errores.push("mcts.simulations debe ser mayor que 0".to_owned());
+        }
+        if self.c_puct < 0.0 {
+            errores.push("mcts.c_puct no puede ser negativo".to_owned());
+        }
+        if self.temperature <= 0.0 {
+            errores.push("mcts.temperature debe ser mayor que 0".to_owned());
+        }
+        if !(0.0..=1.0).contains(&self.bootstrap_weight) {
+            errores.push("mcts.bootstrap_weight debe estar entre 0 y 1".to_owned());
+        }
+
+        errores
+    }
+}
+
+struct Nodo {
+    board: Board,
+    movimiento: Option<ChessMove>,
+    hijos: Vec<usize>,
+    prior: f64,
+    visitas: u32,
+    // Suma de valores desde el punto de vista del bando que hizo el movimiento que lleva aqui
+    valor_total: f64,
+    expandido: bool,
+    // Valor fijo para el bando que mueve en mates, ahogados y repeticiones
+    terminal: Option<f64>,
+}
+
+impl Nodo {
+    fn new(board: Board, movimiento: Option<ChessMove>, prior: f64) -> Nodo {
+        Nodo { board, movimiento, hijos: Vec::new(), prior, visitas: 0, valor_total: 0.0, expandido: false, terminal: None }
+    }
+
+    fn q(&self) -> f64 {
+        if self.visitas == 0 { 0.0 } else { self.valor_total / self.visitas as f64 }
+    }
+}
+
+pub struct MctsResult {
+    pub best_move: ChessMove,
+    // Media de los valores de las simulaciones que pasan por best_move, para el bando que mueve
+    // en la posicion resultante (el mismo punto de vista que el max Q de s' en el TD target)
+    pub next_value: Option<f64>,
+}
+
+// PUCT: priors del softmax de los Q-values legales y valor de la red en las hojas.
+// Devuelve el movimiento mas visitado desde la posicion actual de la partida.
+pub fn search(q_network: &DQNModelNN, device: Device, game_manager: &GameManager, config: &MctsConfig) -> MctsResult {
+    let mut arbol = vec![Nodo::new(game_manager.board(), None, 1.0)];
+    let previas = game_manager.previous_positions();
+
+    for _ in 0..config.simulations.max(1) {
+        let mut camino = vec![0];
+        let mut actual = 0;
+
+        while arbol[actual].expandido && arbol[actual].terminal.is_none() {
+            actual = seleccionar(&arbol, actual, config.c_puct);
+            camino.push(actual);
+        }
+
+        // Valor para el bando que mueve en el nodo hoja
+        let valor = match arbol[actual].terminal {
+            Some(valor) => valor,
+            None => {
+                // La raiz conoce el historial real; dentro del arbol la regla de 50 y las repeticiones van a 0
+                let state = if actual == 0 { game_manager.packed_state() } else { PackedState::from_board(&arbol[actual].board) };
+                let repetida = actual != 0 && {
+                    let hash = arbol[actual].board.get_hash();
+                    previas.contains(&hash) || camino[..camino.len() - 1].iter().any(|i| arbol[*i].board.get_hash() == hash)
+                };
+
+                expandir(&mut arbol, actual, &state, repetida, q_network, device, config.temperature)
+            }
+        };
+
+        let mut valor = valor;
+        for i in camino.iter().rev() {
+            arbol[*i].visitas += 1;
+            arbol[*i].valor_total -= valor;
+            valor = -valor;
+        }
+    }
+
+    match arbol[0].hijos.iter().copied().max_by_key(|i| arbol[*i].visitas) {
+        // valor_total del hijo es del bando que hizo el movimiento: se cambia de signo para el que mueve despues
+        Some(hijo) => MctsResult { best_move: arbol[hijo].movimiento.unwrap(), next_value: Some(-arbol[hijo].q()) },
+        None => MctsResult { best_move: game_manager.get_moves().next().unwrap(), next_value: None },
+    }
+}
+
+fn seleccionar(arbol: &[Nodo], padre: usize, c_puct: f64) -> usize {
+    let raiz_visitas = (arbol[padre].visitas as f64).sqrt();
+    let puct = |i: usize| arbol[i].q() + c_puct * arbol[i].prior * raiz_visitas / (1.0 + arbol[i].visitas as f64);
+
+    arbol[padre].hijos.iter().copied().max_by(|a, b| puct(*a).total_cmp(&puct(*b))).unwrap()
+}
+
+fn expandir(
+    arbol: &mut Vec<Nodo>,
+    indice: usize,
+    state: &PackedState,
+    repetida: bool,
+    q_network: &DQNModelNN,
+    device: Device,
+    temperature: f64,
+) -> f64 {
+    let board = arbol[indice].board;
+    let movimientos: Vec<ChessMove> = MoveGen::new_legal(&board).collect();
+    arbol[indice].expandido = true;
+
+    if repetida || movimientos.is_empty() {
+        let valor = if !repetida && *board.checkers() != EMPTY { -1.0 } else { 0.0 };
+        arbol[indice].terminal = Some(valor);
+        return valor;
+    }
+
+    let (qv, value) = no_grad(|| q_network.q_and_value(&board_to_tensor(state, q_network.encoding()).to_device(device)));
+    let qv = qv.to_device(Device::Cpu).view([-1]);
+    let q_legales: Vec<f64> = movimientos.iter().map(|m| qv.double_value(&[encode_move(*m) as i64])).collect();
+
+    let q_max = q_legales.iter().cloned().fold(f64::NEG_INFINITY, f64::max);
+    let exp: Vec<f64> = q_legales.iter().map(|q| ((q - q_max) / temperature).exp()).collect();
+    let suma: f64 = exp.iter().sum();
+
+    for (movimiento, e) in movimientos.iter().zip(exp.iter()) {
+        arbol.push(Nodo::new(board.make_move_new(*movimiento), Some(*movimiento), e / suma));
+        let hijo = arbol.len() - 1;
+        arbol[indice].hijos.push(hijo);
+    }
+
+    // Sin cabeza dueling el valor se aproxima con el mejor Q legal
+    let valor = match value {
+        Some(v) => v.double_value(&[0]),
+        None => q_max,
+    };
+    valor.clamp(-1.0, 1.0)
+}
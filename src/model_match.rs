@@ -0,0 +1,54 @@
+use std::process::exit;
+
+use chess::Color;
+use tch::Device;
+
+use crate::{game_manager::GameManager, pgn::{game_to_pgn, PgnHeaders}, player::{NetworkPlayer, Player}};
+
+pub fn enfrentar(ruta_blancas: &str, ruta_negras: &str, max_moves: u32, device: Device) {
+    let mut blancas = cargar_jugador(ruta_blancas, device);
+    let mut negras = cargar_jugador(ruta_negras, device);
+
+    let mut game_manager = GameManager::new();
+    jugar_partida(&mut game_manager, blancas.as_mut(), negras.as_mut(), max_moves);
+
+    let headers = PgnHeaders::new("DQNChess match", &blancas.nombre(), &negras.nombre());
+    println!("{}", game_to_pgn(&game_manager, &headers, &[]));
+    match game_manager.result() {
+        Some(resultado) => println!("Resultado: {:?}", resultado),
+        None => println!("Resultado: tablas por limite de {max_moves} movimientos"),
+    }
+}
+
+pub fn cargar_jugador(ruta: &str, device: Device) -> Box<dyn Player> {
+    match NetworkPlayer::load(ruta, device) {
+        Ok(jugador) => Box::new(jugador),
+        Err(e) => {
+            println!("{e}");
+            exit(1);
+        }
+    }
+}
+
+// Juega desde la posicion actual hasta el final o el limite de movimientos; devuelve los movimientos jugados
+pub fn jugar_partida(
+    game_manager: &mut GameManager,
+    blancas: &mut dyn Player,
+    negras: &mut dyn Player,
+    max_moves: u32,
+) -> u32 {
+    let mut moves_cont = 0;
+
+    while game_manager.result().is_none() && moves_cont < max_moves {
+        let movimiento = match game_manager.side_to_move() {
+            Color::White => blancas.choose_move(game_manager),
+            Color::Black => negras.choose_move(game_manager),
+        };
+
+        game_manager.do_move(movimiento);
+        game_manager.declare_draw();
+        moves_cont += 1;
+    }
+
+    moves_cont
+}
@@ -0,0 +1,219 @@
+use chess::{Board, ChessMove, Piece, Square, ALL_SQUARES};
+use tch::Tensor;
+
+// Codificacion estilo AlphaZero: 64 casillas origen x 73 planos
+//  - 0..56: movimientos de dama (8 direcciones x 7 distancias)
+//  - 56..64: saltos de caballo
+//  - 64..73: subpromociones (caballo, alfil, torre) x (captura izq, avance, captura der)
+// La promocion a dama se codifica como un movimiento de dama normal.
+pub const NUM_PLANES: usize = 73;
+pub const NUM_ACTIONS: usize = 64 * NUM_PLANES;
+
+const QUEEN_DIRECTIONS: [(i32, i32); 8] = [
+    (1, 0), (1, 1), (0, 1), (-1, 1), (-1, 0), (-1, -1), (0, -1), (1, -1),
+];
+
+const KNIGHT_OFFSETS: [(i32, i32); 8] = [
+    (2, 1), (1, 2), (-1, 2), (-2, 1), (-2, -1), (-1, -2), (1, -2), (2, -1),
+];
+
+const UNDERPROMOTIONS: [Piece; 3] = [Piece::Knight, Piece::Bishop, Piece::Rook];
+
+const KNIGHT_PLANE: usize = 56;
+const UNDERPROMOTION_PLANE: usize = 64;
+
+pub fn encode_move(chess_move: ChessMove) -> usize {
+    let source = chess_move.get_source();
+    let dest = chess_move.get_dest();
+
+    let d_rank = dest.get_rank().to_index() as i32 - source.get_rank().to_index() as i32;
+    let d_file = dest.get_file().to_index() as i32 - source.get_file().to_index() as i32;
+
+    let plane = match chess_move.get_promotion() {
+        Some(p) if p != Piece::Queen => {
+            let piece_index = UNDERPROMOTIONS.iter().position(|u| *u == p).unwrap();
+            UNDERPROMOTION_PLANE + piece_index * 3 + (d_file + 1) as usize
+        }
+        _ => match KNIGHT_OFFSETS.iter().position(|o| *o == (d_rank, d_file)) {
+            Some(k) => KNIGHT_PLANE + k,
+            None => {
+                let direction = QUEEN_DIRECTIONS
+                    .iter()
+                    .position(|d| *d == (d_rank.signum(), d_file.signum()))
+                    .unwrap();
+                let distance = i32::max(d_rank.abs(), d_file.abs()) as usize;
+                direction * 7 + distance - 1
+            }
+        },
+    };
+
+    source.to_index() * NUM_PLANES + plane
+}
+
+// Devuelve el movimiento solo si es legal en la posicion dada
+pub fn decode_move(board: &Board, action: usize) -> Option<ChessMove> {
+    if action >= NUM_ACTIONS {
+        return None;
+    }
+
+    let source = ALL_SQUARES[action / NUM_PLANES];
+    let plane = action % NUM_PLANES;
+
+    let (d_rank, d_file, underpromotion) = if plane < KNIGHT_PLANE {
+        let (dr, df) = QUEEN_DIRECTIONS[plane / 7];
+        let distance = (plane % 7) as i32 + 1;
+        (dr * distance, df * distance, None)
+    }
+    else if plane < UNDERPROMOTION_PLANE {
+        let (dr, df) = KNIGHT_OFFSETS[plane - KNIGHT_PLANE];
+        (dr, df, None)
+    }
+    else {
+        let index = plane - UNDERPROMOTION_PLANE;
+        let dr = match board.side_to_move() {
+            chess::Color::White => 1,
+            chess::Color::Black => -1,
+        };
+        (dr, (index % 3) as i32 - 1, Some(UNDERPROMOTIONS[index / 3]))
+    };
+
+    let dest = offset_square(source, d_rank, d_file)?;
+
+    let promotion = match underpromotion {
+        Some(p) => Some(p),
+        None => {
+            let last_rank = dest.get_rank().to_index() == 0 || dest.get_rank().to_index() == 7;
+            if last_rank && board.piece_on(source) == Some(Piece::Pawn) {
+                Some(Piece::Queen)
+            }
+            else {
+                None
+            }
+        }
+    };
+
+    let chess_move = ChessMove::new(source, dest, promotion);
+
+    if board.legal(chess_move) {
+        Some(chess_move)
+    }
+    else {
+        None
+    }
+}
+
+fn offset_square(square: Square, d_rank: i32, d_file: i32) -> Option<Square> {
+    let rank = square.get_rank().to_index() as i32 + d_rank;
+    let file = square.get_file().to_index() as i32 + d_file;
+
+    if (0..8).contains(&rank) && (0..8).contains(&file) {
+        Some(ALL_SQUARES[(rank * 8 + file) as usize])
+    }
+    else {
+        None
+    }
+}
+
+// Mascara booleana [NUM_ACTIONS] con las acciones legales
+pub fn legal_mask(legal_actions: &[u16]) -> Tensor {
+    let mut mask = vec![false; NUM_ACTIONS];
+    for action in legal_actions {
+        mask[*action as usize] = true;
+    }
+
+    Tensor::from_slice(mask.as_slice())
+}
+
+// Mascara [batch, NUM_ACTIONS] para un lote de estados
+pub fn legal_mask_batch(batch: &[&[u16]]) -> Tensor {
+    let mut mask = vec![false; batch.len() * NUM_ACTIONS];
+    for (i, legal_actions) in batch.iter().enumerate() {
+        for action in legal_actions.iter() {
+            mask[i * NUM_ACTIONS + *action as usize] = true;
+        }
+    }
+
+    Tensor::from_slice(mask.as_slice()).view([batch.len() as i64, NUM_ACTIONS as i64])
+}
+
+// Las acciones ilegales quedan a -inf para que nunca ganen un argmax/max
+pub fn mask_illegal(q_values: &Tensor, mask: &Tensor) -> Tensor {
+    q_values.masked_fill(&mask.logical_not(), f64::NEG_INFINITY)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{collections::HashSet, str::FromStr};
+
+    use chess::{Board, MoveGen, Piece};
+    use tch::Kind;
+
+    use super::*;
+
+    // Inicial, kiwipete (enroques), captura al paso y promociones de ambos bandos
+    const POSICIONES: [&str; 6] = [
+        "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1",
+        "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+        "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R b KQkq - 0 1",
+        "4k3/8/8/3pP3/8/8/8/4K3 w - d6 0 1",
+        "n1n1k3/1P6/8/8/8/8/8/4K3 w - - 0 1",
+        "4k3/8/8/8/8/8/6p1/4KN1N b - - 0 1",
+    ];
+
+    fn boards() -> Vec<Board> {
+        POSICIONES.iter().map(|fen| Board::from_str(fen).unwrap()).collect()
+    }
+
+    #[test]
+    fn encode_decode_roundtrip() {
+        for board in boards() {
+            let mut acciones = HashSet::new();
+
+            for chess_move in MoveGen::new_legal(&board) {
+                let action = encode_move(chess_move);
+                assert!(action < NUM_ACTIONS);
+                assert!(acciones.insert(action), "accion repetida para {chess_move} en {board}");
+                assert_eq!(decode_move(&board, action), Some(chess_move), "{chess_move} en {board}");
+            }
+        }
+    }
+
+    #[test]
+    fn roundtrip_covers_special_moves() {
+        let movimientos: Vec<ChessMove> = boards().iter().flat_map(|b| MoveGen::new_legal(b).collect::<Vec<_>>()).collect();
+
+        // Enroques, captura al paso y las cuatro promociones, de frente y capturando
+        for san in ["e1g1", "e1c1", "e8g8", "e8c8", "e5d6"] {
+            assert!(movimientos.iter().any(|m| m.to_string() == san), "falta {san}");
+        }
+        for pieza in [Piece::Queen, Piece::Rook, Piece::Bishop, Piece::Knight] {
+            assert_eq!(movimientos.iter().filter(|m| m.get_promotion() == Some(pieza)).count(), 6);
+        }
+    }
+
+    #[test]
+    fn decode_rejects_illegal_actions() {
+        let board = Board::default();
+        let legales: HashSet<usize> = MoveGen::new_legal(&board).map(encode_move).collect();
+
+        for action in 0..NUM_ACTIONS {
+            assert_eq!(decode_move(&board, action).is_some(), legales.contains(&action));
+        }
+        assert_eq!(decode_move(&board, NUM_ACTIONS), None);
+    }
+
+    #[test]
+    fn legal_mask_matches_movegen() {
+        for board in boards() {
+            let acciones: Vec<u16> = MoveGen::new_legal(&board).map(|m| encode_move(m) as u16).collect();
+            let mask = legal_mask(&acciones);
+
+            assert_eq!(mask.size(), [NUM_ACTIONS as i64]);
+            assert_eq!(mask.sum(Kind::Int64).int64_value(&[]), MoveGen::new_legal(&board).len() as i64);
+
+            let batch = legal_mask_batch(&[acciones.as_slice(), acciones.as_slice()]);
+            assert_eq!(batch.size(), [2, NUM_ACTIONS as i64]);
+            assert!(batch.get(1).equal(&mask));
+        }
+    }
+}
@@ -0,0 +1,179 @@
+use std::collections::VecDeque;
+
+use chess::Color;
+
+use crate::{replay_buffer::BufferElement, train_config::{TargetMode, TrainConfig}};
+
+// Agrupa transiciones consecutivas en retornos a n pasos:
+// R = r_0 + (s*gamma) r_1 + ... + (s*gamma)^(n-1) r_(n-1), con s = -1 si las recompensas
+// alternan de punto de vista (negamax) y s = 1 en otro caso.
+pub struct NStepAccumulator {
+    n: usize,
+    factor: f64,
+    pendientes: VecDeque<BufferElement>,
+}
+
+impl NStepAccumulator {
+    pub fn new(n: usize, gamma: f64, alternate_sign: bool) -> NStepAccumulator {
+        let signo = if alternate_sign { -1.0 } else { 1.0 };
+
+        NStepAccumulator {
+            n,
+            factor: signo * gamma,
+            pendientes: VecDeque::with_capacity(n),
+        }
+    }
+
+    // Devuelve las transiciones completas; al terminar la partida se vacia la cola
+    pub fn push(&mut self, transition: BufferElement) -> Vec<BufferElement> {
+        let done = transition.done;
+        let mut completas = Vec::new();
+
+        self.pendientes.push_back(transition);
+
+        if self.pendientes.len() == self.n {
+            completas.push(self.combine());
+            self.pendientes.pop_front();
+        }
+
+        if done {
+            while !self.pendientes.is_empty() {
+                completas.push(self.combine());
+                self.pendientes.pop_front();
+            }
+        }
+
+        completas
+    }
+
+    fn combine(&self) -> BufferElement {
+        let first = self.pendientes.front().unwrap();
+        let last = self.pendientes.back().unwrap();
+
+        let mut reward = 0.0;
+        let mut descuento = 1.0;
+        for t in self.pendientes.iter() {
+            reward += descuento * t.reward as f64;
+            descuento *= self.factor;
+        }
+
+        BufferElement {
+            actual_state: first.actual_state,
+            action: first.action,
+            reward: reward as f32,
+            next_state: last.next_state,
+            next_legal_actions: last.next_legal_actions.clone(),
+            done: last.done,
+            next_value: last.next_value,
+        }
+    }
+}
+
+// Convierte las transiciones de una partida en las que van al buffer segun el target mode
+pub struct GameTransitions {
+    opponent_reply: bool,
+    // Transiciones a la espera de la respuesta del rival (modo opponent_reply)
+    pendientes: [Option<BufferElement>; 2],
+    // En opponent_reply cada bando acumula su propia secuencia de transiciones
+    acumuladores: [NStepAccumulator; 2],
+}
+
+impl GameTransitions {
+    pub fn new(config: &TrainConfig) -> GameTransitions {
+        let negamax = config.target_mode == TargetMode::Negamax;
+
+        GameTransitions {
+            opponent_reply: config.target_mode == TargetMode::OpponentReply,
+            pendientes: [None, None],
+            acumuladores: [
+                NStepAccumulator::new(config.n_step, config.gamma, negamax),
+                NStepAccumulator::new(config.n_step, config.gamma, negamax),
+            ],
+        }
+    }
+
+    // transition.reward es la del bando que mueve; la del rival es la misma cambiada de signo
+    pub fn push(&mut self, transition: BufferElement, mover: Color) -> Vec<BufferElement> {
+        if !self.opponent_reply {
+            return self.acumuladores[0].push(transition);
+        }
+
+        let mut completas = Vec::new();
+
+        // La transicion pendiente del rival se cierra con la posicion tras nuestra respuesta
+        if let Some(mut pendiente) = self.pendientes[(!mover).to_index()].take() {
+            pendiente.reward = -transition.reward;
+            pendiente.next_state = transition.next_state;
+            pendiente.next_legal_actions = transition.next_legal_actions.clone();
+            pendiente.done = transition.done;
+            pendiente.next_value = transition.next_value;
+            completas.extend(self.acumuladores[(!mover).to_index()].push(pendiente));
+        }
+
+        if transition.done {
+            completas.extend(self.acumuladores[mover.to_index()].push(transition));
+        }
+        else {
+            self.pendientes[mover.to_index()] = Some(transition);
+        }
+
+        completas
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::transition;
+
+    #[test]
+    fn full_window_returns_discounted_sum() {
+        let mut acumulador = NStepAccumulator::new(3, 0.5, false);
+
+        assert!(acumulador.push(transition(1.0, 0, false)).is_empty());
+        assert!(acumulador.push(transition(2.0, 1, false)).is_empty());
+
+        // El valor MCTS de arranque es el del ultimo estado, igual que next_state
+        let mut ultima = transition(4.0, 2, false);
+        ultima.next_value = Some(0.5);
+        let completas = acumulador.push(ultima);
+        assert_eq!(completas.len(), 1);
+        assert_eq!(completas[0].reward, 1.0 + 0.5 * 2.0 + 0.25 * 4.0);
+        assert_eq!(completas[0].action, 0);
+        assert_eq!(completas[0].next_legal_actions, vec![2]);
+        assert_eq!(completas[0].next_value, Some(0.5));
+        assert!(!completas[0].done);
+
+        // La ventana avanza de una en una
+        let completas = acumulador.push(transition(0.0, 3, false));
+        assert_eq!(completas[0].reward, 2.0 + 0.5 * 4.0);
+        assert_eq!(completas[0].action, 1);
+    }
+
+    #[test]
+    fn episode_end_flushes_shorter_returns() {
+        let mut acumulador = NStepAccumulator::new(3, 0.5, false);
+        acumulador.push(transition(1.0, 0, false));
+
+        let completas = acumulador.push(transition(2.0, 1, true));
+        let rewards: Vec<f32> = completas.iter().map(|t| t.reward).collect();
+        assert_eq!(rewards, vec![1.0 + 0.5 * 2.0, 2.0]);
+        assert!(completas.iter().all(|t| t.done && t.next_legal_actions == vec![1]));
+
+        // Tras el final la cola queda vacia para la siguiente partida
+        assert!(acumulador.push(transition(1.0, 2, false)).is_empty());
+    }
+
+    #[test]
+    fn negamax_alternates_sign() {
+        let mut acumulador = NStepAccumulator::new(3, 0.5, true);
+        acumulador.push(transition(0.0, 0, false));
+        acumulador.push(transition(0.0, 1, false));
+
+        // Mate en la tercera jugada: bueno para quien la hace y para quien movio dos antes,
+        // malo para el que movio entre medias
+        let completas = acumulador.push(transition(1.0, 2, true));
+        let rewards: Vec<f32> = completas.iter().map(|t| t.reward).collect();
+        assert_eq!(rewards, vec![0.25, -0.5, 1.0]);
+    }
+}
@@ -0,0 +1,431 @@
+use std::{
+    fs::{self, OpenOptions},
+    io::{self, Write},
+    path::Path,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use chess::{Board, ChessMove, Color, GameResult, MoveGen, Piece, Square, EMPTY};
+
+use crate::game_manager::GameManager;
+
+// Longitud maxima de las lineas del texto de movimientos
+const ANCHO_LINEA: usize = 80;
+
+// Seven-tag roster; el resultado sale de la partida
+pub struct PgnHeaders {
+    pub event: String,
+    pub site: String,
+    pub date: String,
+    pub round: String,
+    pub white: String,
+    pub black: String,
+}
+
+impl PgnHeaders {
+    pub fn new(event: &str, white: &str, black: &str) -> PgnHeaders {
+        PgnHeaders {
+            event: event.to_owned(),
+            site: "DQNChess".to_owned(),
+            date: fecha_actual(),
+            round: "-".to_owned(),
+            white: white.to_owned(),
+            black: black.to_owned(),
+        }
+    }
+}
+
+// Partida leida de un fichero PGN: movimientos en SAN, sin comentarios ni variantes
+pub struct PgnGame {
+    pub tags: Vec<(String, String)>,
+    pub moves: Vec<String>,
+    // "1-0", "0-1", "1/2-1/2" o "*"
+    pub result: String,
+}
+
+impl PgnGame {
+    pub fn tag(&self, nombre: &str) -> Option<&str> {
+        self.tags.iter().find(|(tag, _)| tag == nombre).map(|(_, valor)| valor.as_str())
+    }
+}
+
+pub fn load_pgn<P: AsRef<Path>>(ruta: P) -> Result<Vec<PgnGame>, String> {
+    let texto = fs::read_to_string(ruta.as_ref()).map_err(|e| format!("No se pudo leer {}: {e}", ruta.as_ref().display()))?;
+    Ok(parse_pgn(&texto))
+}
+
+pub fn parse_pgn(texto: &str) -> Vec<PgnGame> {
+    let mut partidas = Vec::new();
+    let mut actual = PgnGame { tags: Vec::new(), moves: Vec::new(), result: "*".to_owned() };
+    let mut en_comentario = false;
+    let mut variantes: u32 = 0;
+
+    for linea in texto.lines() {
+        let linea = linea.trim();
+
+        if !en_comentario && variantes == 0 && linea.starts_with('[') {
+            // Una cabecera despues de movimientos sin resultado empieza otra partida
+            if !actual.moves.is_empty() {
+                partidas.push(std::mem::replace(&mut actual, PgnGame { tags: Vec::new(), moves: Vec::new(), result: "*".to_owned() }));
+            }
+            if let Some(tag) = parse_tag(linea) {
+                actual.tags.push(tag);
+            }
+            continue;
+        }
+        // Lineas de escape
+        if linea.starts_with('%') {
+            continue;
+        }
+
+        let mut token = String::new();
+        // El espacio final cierra el ultimo token de la linea
+        for c in linea.chars().chain(std::iter::once(' ')) {
+            if en_comentario {
+                en_comentario = c != '}';
+                continue;
+            }
+
+            match c {
+                '{' | '(' | ')' | ';' | ' ' | '\t' => {
+                    if variantes == 0 && !token.is_empty() {
+                        if es_resultado(&token) {
+                            actual.result = token.clone();
+                            partidas.push(std::mem::replace(&mut actual, PgnGame { tags: Vec::new(), moves: Vec::new(), result: "*".to_owned() }));
+                        }
+                        else if let Some(san) = limpiar_token(&token) {
+                            actual.moves.push(san);
+                        }
+                    }
+                    token.clear();
+
+                    match c {
+                        '{' => en_comentario = true,
+                        '(' => variantes += 1,
+                        ')' => variantes = variantes.saturating_sub(1),
+                        // Comentario hasta el final de la linea
+                        ';' => break,
+                        _ => {}
+                    }
+                }
+                _ => token.push(c),
+            }
+        }
+    }
+
+    if !actual.moves.is_empty() {
+        partidas.push(actual);
+    }
+
+    partidas
+}
+
+// Movimiento SAN de un PGN (con anotaciones como + o !?) en board.
+// ChessMove::from_san no acepta "=" en las promociones ni el enroque con ceros.
+pub fn parse_san(board: &Board, san: &str) -> Option<ChessMove> {
+    let san = san.trim_end_matches(['+', '#', '!', '?']).replace('=', "").replace('0', "O");
+    ChessMove::from_san(board, &san).ok()
+}
+
+fn parse_tag(linea: &str) -> Option<(String, String)> {
+    let contenido = linea.strip_prefix('[')?.strip_suffix(']')?.trim();
+    let (nombre, valor) = contenido.split_once(char::is_whitespace)?;
+    let valor = valor.trim().strip_prefix('"')?.strip_suffix('"')?;
+
+    Some((nombre.to_owned(), valor.replace("\\\"", "\"").replace("\\\\", "\\")))
+}
+
+fn es_resultado(token: &str) -> bool {
+    matches!(token, "1-0" | "0-1" | "1/2-1/2" | "*")
+}
+
+// Quita el numero de jugada ("12." o "12...") y descarta NAGs ($1); None si no queda movimiento
+fn limpiar_token(token: &str) -> Option<String> {
+    // SAN nunca lleva puntos: lo que vaya antes del ultimo es el numero de jugada
+    let san = token.rsplit('.').next().unwrap_or(token);
+
+    if san.is_empty() || san.starts_with('$') {
+        None
+    }
+    else {
+        Some(san.to_owned())
+    }
+}
+
+// comentarios: uno opcional por cada movimiento de la partida, en orden
+pub fn game_to_pgn(game_manager: &GameManager, headers: &PgnHeaders, comentarios: &[Option<String>]) -> String {
+    let resultado = result_tag(game_manager.result());
+    let inicial = game_manager.initial_board();
+
+    let mut pgn = String::new();
+    for (tag, valor) in [
+        ("Event", &headers.event),
+        ("Site", &headers.site),
+        ("Date", &headers.date),
+        ("Round", &headers.round),
+        ("White", &headers.white),
+        ("Black", &headers.black),
+    ] {
+        pgn.push_str(&format!("[{tag} \"{}\"]\n", escapar(valor)));
+    }
+    pgn.push_str(&format!("[Result \"{resultado}\"]\n"));
+    if inicial != Board::default() || game_manager.initial_fullmove() != 1 {
+        pgn.push_str(&format!("[SetUp \"1\"]\n[FEN \"{}\"]\n", game_manager.initial_fen()));
+    }
+    pgn.push('\n');
+
+    let mut tokens: Vec<String> = Vec::new();
+    let mut board = inicial;
+    for (i, movimiento) in game_manager.moves().into_iter().enumerate() {
+        // Se cuenta desde el numero de jugada del FEN de partida
+        let numero = game_manager.initial_fullmove() as usize + (i + usize::from(inicial.side_to_move() == Color::Black)) / 2;
+        if board.side_to_move() == Color::White {
+            tokens.push(format!("{numero}."));
+        }
+        else if i == 0 {
+            tokens.push(format!("{numero}..."));
+        }
+
+        tokens.push(san(&board, movimiento));
+        if let Some(Some(comentario)) = comentarios.get(i) {
+            tokens.push(format!("{{{}}}", comentario.replace('}', ")")));
+        }
+
+        board = board.make_move_new(movimiento);
+    }
+    tokens.push(resultado.to_owned());
+
+    let mut linea = String::new();
+    for token in tokens {
+        if !linea.is_empty() && linea.len() + 1 + token.len() > ANCHO_LINEA {
+            pgn.push_str(&linea);
+            pgn.push('\n');
+            linea.clear();
+        }
+        if !linea.is_empty() {
+            linea.push(' ');
+        }
+        linea.push_str(&token);
+    }
+    pgn.push_str(&linea);
+    pgn.push('\n');
+
+    pgn
+}
+
+pub fn append_pgn<P: AsRef<Path>>(ruta: P, pgn: &str) -> io::Result<()> {
+    let mut fichero = OpenOptions::new().create(true).append(true).open(ruta)?;
+    writeln!(fichero, "{pgn}")
+}
+
+pub fn result_tag(resultado: Option<GameResult>) -> &'static str {
+    match resultado {
+        Some(GameResult::WhiteCheckmates) | Some(GameResult::BlackResigns) => "1-0",
+        Some(GameResult::BlackCheckmates) | Some(GameResult::WhiteResigns) => "0-1",
+        Some(GameResult::Stalemate) | Some(GameResult::DrawAccepted) | Some(GameResult::DrawDeclared) => "1/2-1/2",
+        None => "*",
+    }
+}
+
+// Notacion algebraica estandar de un movimiento legal en board
+pub fn san(board: &Board, movimiento: ChessMove) -> String {
+    let origen = movimiento.get_source();
+    let destino = movimiento.get_dest();
+    let pieza = board.piece_on(origen).unwrap_or(Piece::Pawn);
+
+    let mut res = if pieza == Piece::King && origen.get_file().to_index().abs_diff(destino.get_file().to_index()) == 2 {
+        if destino.get_file().to_index() > origen.get_file().to_index() { "O-O".to_owned() } else { "O-O-O".to_owned() }
+    }
+    else if pieza == Piece::Pawn {
+        let mut res = String::new();
+        if origen.get_file() != destino.get_file() {
+            res.push(columna(origen));
+            res.push('x');
+        }
+        res.push_str(&destino.to_string());
+        if let Some(promocion) = movimiento.get_promotion() {
+            res.push('=');
+            res.push(letra(promocion));
+        }
+        res
+    }
+    else {
+        let mut res = String::from(letra(pieza));
+        res.push_str(&desambiguar(board, movimiento, pieza));
+        if board.piece_on(destino).is_some() {
+            res.push('x');
+        }
+        res.push_str(&destino.to_string());
+        res
+    };
+
+    let siguiente = board.make_move_new(movimiento);
+    if *siguiente.checkers() != EMPTY {
+        res.push(if MoveGen::new_legal(&siguiente).len() == 0 { '#' } else { '+' });
+    }
+
+    res
+}
+
+// Columna, fila o casilla de origen si otra pieza igual puede ir al mismo destino
+fn desambiguar(board: &Board, movimiento: ChessMove, pieza: Piece) -> String {
+    let origen = movimiento.get_source();
+    let rivales: Vec<Square> = MoveGen::new_legal(board)
+        .filter(|m| m.get_dest() == movimiento.get_dest() && m.get_source() != origen && board.piece_on(m.get_source()) == Some(pieza))
+        .map(|m| m.get_source())
+        .collect();
+
+    if rivales.is_empty() {
+        String::new()
+    }
+    else if rivales.iter().all(|s| s.get_file() != origen.get_file()) {
+        columna(origen).to_string()
+    }
+    else if rivales.iter().all(|s| s.get_rank() != origen.get_rank()) {
+        (origen.get_rank().to_index() + 1).to_string()
+    }
+    else {
+        origen.to_string()
+    }
+}
+
+fn columna(casilla: Square) -> char {
+    (b'a' + casilla.get_file().to_index() as u8) as char
+}
+
+fn letra(pieza: Piece) -> char {
+    match pieza {
+        Piece::Pawn => 'P',
+        Piece::Knight => 'N',
+        Piece::Bishop => 'B',
+        Piece::Rook => 'R',
+        Piece::Queen => 'Q',
+        Piece::King => 'K',
+    }
+}
+
+fn escapar(valor: &str) -> String {
+    valor.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+// Fecha UTC en formato PGN (AAAA.MM.DD)
+fn fecha_actual() -> String {
+    let Ok(duracion) = SystemTime::now().duration_since(UNIX_EPOCH) else {
+        return "????.??.??".to_owned();
+    };
+
+    // Conversion de dias desde 1970-01-01 a fecha civil (algoritmo de Howard Hinnant)
+    let dias = (duracion.as_secs() / 86400) as i64 + 719468;
+    let era = dias.div_euclid(146097);
+    let dia_era = dias.rem_euclid(146097);
+    let anyo_era = (dia_era - dia_era / 1460 + dia_era / 36524 - dia_era / 146096) / 365;
+    let dia_anyo = dia_era - (365 * anyo_era + anyo_era / 4 - anyo_era / 100);
+    let mp = (5 * dia_anyo + 2) / 153;
+    let dia = dia_anyo - (153 * mp + 2) / 5 + 1;
+    let mes = if mp < 10 { mp + 3 } else { mp - 9 };
+    let anyo = anyo_era + era * 400 + i64::from(mes <= 2);
+
+    format!("{anyo:04}.{mes:02}.{dia:02}")
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use chess::{Board, ChessMove};
+
+    use super::*;
+
+    fn movimiento(uci: &str) -> ChessMove {
+        ChessMove::from_str(uci).unwrap()
+    }
+
+    #[test]
+    fn san_special_moves() {
+        for (fen, uci, esperado) in [
+            ("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1", "g1f3", "Nf3"),
+            ("4k3/8/8/8/8/8/8/R3K2R w KQ - 0 1", "e1g1", "O-O"),
+            ("4k3/8/8/8/8/8/8/R3K2R w KQ - 0 1", "e1c1", "O-O-O"),
+            ("r3k3/1P6/8/8/8/8/8/4K3 w - - 0 1", "b7a8q", "bxa8=Q+"),
+            ("r3k3/1P6/8/8/8/8/8/4K3 w - - 0 1", "b7b8n", "b8=N"),
+            ("4k3/8/8/8/8/8/8/1N1K1N2 w - - 0 1", "b1d2", "Nbd2"),
+            ("4k3/8/8/R7/8/8/8/R3K3 w - - 0 1", "a1a3", "R1a3"),
+            ("4k3/8/8/8/8/Q7/8/Q1Q1K3 w - - 0 1", "a1b2", "Qa1b2"),
+            ("k7/8/1K6/8/8/8/8/7R w - - 0 1", "h1h8", "Rh8#"),
+        ] {
+            let board = Board::from_str(fen).unwrap();
+            assert_eq!(san(&board, movimiento(uci)), esperado, "{fen}");
+            assert_eq!(parse_san(&board, esperado), Some(movimiento(uci)), "{fen}");
+        }
+    }
+
+    #[test]
+    fn parse_san_accepts_pgn_variants() {
+        let board = Board::from_str("4k3/8/8/8/8/8/8/R3K2R w KQ - 0 1").unwrap();
+        assert_eq!(parse_san(&board, "0-0"), Some(movimiento("e1g1")));
+        assert_eq!(parse_san(&board, "O-O-O!?"), Some(movimiento("e1c1")));
+
+        let board = Board::from_str("r3k3/1P6/8/8/8/8/8/4K3 w - - 0 1").unwrap();
+        assert_eq!(parse_san(&board, "bxa8Q+"), Some(movimiento("b7a8q")));
+        assert_eq!(parse_san(&board, "Ke9"), None);
+    }
+
+    #[test]
+    fn pgn_roundtrip() {
+        // Desambiguacion (Nbd2, Nbd7), enroque por los dos lados y capturas
+        let ucis = [
+            "d2d4", "d7d5", "g1f3", "g8f6", "c1f4", "c8f5", "e2e3", "e7e6", "b1d2", "b8d7",
+            "f1e2", "f8d6", "e1g1", "d8e7", "c2c4", "e8c8", "f4d6", "e7d6",
+        ];
+
+        let mut board = Board::default();
+        let mut texto = String::from("[Event \"Prueba\"]\n[White \"A \\\"B\\\"\"]\n\n");
+        for (i, uci) in ucis.iter().enumerate() {
+            if i % 2 == 0 {
+                texto.push_str(&format!("{}. ", i / 2 + 1));
+            }
+            texto.push_str(&san(&board, movimiento(uci)));
+            // Comentarios, variantes y NAGs que el parser debe ignorar
+            match i {
+                3 => texto.push_str(" {comentario (con parentesis)} $1"),
+                7 => texto.push_str(" (4... c5 5. dxc5) ; resto de linea\n"),
+                _ => texto.push(' '),
+            }
+            board = board.make_move_new(movimiento(uci));
+        }
+        texto.push_str("1-0\n\n[Event \"Otra\"]\n\n1. e4 *\n");
+
+        let partidas = parse_pgn(&texto);
+        assert_eq!(partidas.len(), 2);
+        assert_eq!(partidas[0].tag("White"), Some("A \"B\""));
+        assert_eq!(partidas[0].result, "1-0");
+        assert_eq!(partidas[0].moves[12], "O-O");
+        assert_eq!(partidas[0].moves[15], "O-O-O");
+        assert_eq!(partidas[1].moves, vec!["e4".to_owned()]);
+        assert_eq!(partidas[1].result, "*");
+
+        let mut board = Board::default();
+        for (san, uci) in partidas[0].moves.iter().zip(ucis.iter()) {
+            let leido = parse_san(&board, san).unwrap();
+            assert_eq!(leido, movimiento(uci), "{san}");
+            board = board.make_move_new(leido);
+        }
+        assert_eq!(partidas[0].moves.len(), ucis.len());
+    }
+
+    #[test]
+    fn fen_header_keeps_move_counters() {
+        let fen = "4k3/8/8/8/8/8/4P3/4K3 b - - 7 42";
+        let mut game_manager = GameManager::from_fen(fen).unwrap();
+        game_manager.do_move(movimiento("e8d7"));
+        game_manager.do_move(movimiento("e2e4"));
+
+        let pgn = game_to_pgn(&game_manager, &PgnHeaders::new("Prueba", "A", "B"), &[]);
+        assert!(pgn.contains(&format!("[FEN \"{fen}\"]")), "{pgn}");
+        assert!(pgn.contains("42... Kd7 43. e4 *"), "{pgn}");
+
+        // Se vuelve a leer con los mismos contadores
+        let partida = &parse_pgn(&pgn)[0];
+        assert_eq!(GameManager::from_fen(partida.tag("FEN").unwrap()).unwrap().initial_fen(), fen);
+    }
+}
@@ -0,0 +1,184 @@
+use chess::{ChessMove, Color, EMPTY};
+use clap::ValueEnum;
+use tch::{nn::{ModuleT, VarStore}, no_grad, Device};
+
+use crate::{
+    board_controller::board_to_tensor,
+    dqn_nn_model::{load_model, DQNModelNN},
+    evaluator::MAX_REWARD,
+    game_manager::GameManager,
+    move_encoding::{decode_move, legal_mask, mask_illegal},
+    hybrid_search::{HybridMode, NetworkHooks},
+    mcts::{self, MctsConfig},
+    search::{ClassicalHooks, Engine, SearchHooks, SearchLimits},
+};
+
+// Cualquier cosa capaz de elegir un movimiento legal: la red, un motor de busqueda...
+pub trait Player {
+    fn nombre(&self) -> String;
+    fn choose_move(&mut self, game_manager: &GameManager) -> ChessMove;
+
+    // Valoracion de la posicion desde el punto de vista de las blancas, si el jugador tiene una
+    fn value(&mut self, _game_manager: &GameManager) -> Option<f64> {
+        None
+    }
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug)]
+pub enum PlayerKind {
+    // Movimiento con mayor Q de la red
+    Network,
+    // Motor alpha-beta sobre evaluator::evaluate
+    AlphaBeta,
+    // Alpha-beta con la red ordenando movimientos y/o puntuando hojas
+    Hybrid,
+    // MCTS con priors y valor de la red
+    Mcts,
+}
+
+// Bits de la tabla de transposicion de los motores de busqueda (2^20 entradas)
+const TT_BITS: u32 = 20;
+
+pub fn create_player(
+    kind: PlayerKind,
+    ruta_nn: &str,
+    limits: SearchLimits,
+    hybrid_mode: HybridMode,
+    mcts_config: &MctsConfig,
+    device: Device,
+) -> Result<Box<dyn Player>, String> {
+    match kind {
+        PlayerKind::Network => Ok(Box::new(NetworkPlayer::load(ruta_nn, device)?)),
+        PlayerKind::AlphaBeta => {
+            let nombre = format!("alpha-beta (profundidad {})", limits.depth);
+            Ok(Box::new(SearchPlayer::new(nombre, ClassicalHooks, limits)))
+        }
+        PlayerKind::Hybrid => {
+            let (vs, q_network) = load_model(ruta_nn, device)?;
+            let nombre = format!("{ruta_nn} + alpha-beta {hybrid_mode:?} (profundidad {})", limits.depth);
+            Ok(Box::new(SearchPlayer::new(nombre, NetworkHooks::new(vs, q_network, device, hybrid_mode), limits)))
+        }
+        PlayerKind::Mcts => {
+            let network = NetworkPlayer::load(ruta_nn, device)?;
+            Ok(Box::new(MctsPlayer { network, config: mcts_config.clone() }))
+        }
+    }
+}
+
+// Juega siempre la accion legal con mayor Q
+pub struct NetworkPlayer {
+    ruta: String,
+    _vs: VarStore,
+    q_network: DQNModelNN,
+    device: Device,
+}
+
+impl NetworkPlayer {
+    pub fn load(ruta: &str, device: Device) -> Result<NetworkPlayer, String> {
+        let (vs, q_network) = load_model(ruta, device)?;
+
+        Ok(NetworkPlayer { ruta: ruta.to_owned(), _vs: vs, q_network, device })
+    }
+}
+
+impl Player for NetworkPlayer {
+    fn nombre(&self) -> String {
+        self.ruta.clone()
+    }
+
+    fn choose_move(&mut self, game_manager: &GameManager) -> ChessMove {
+        greedy_move(&self.q_network, game_manager, self.device)
+    }
+
+    fn value(&mut self, game_manager: &GameManager) -> Option<f64> {
+        Some(network_value(&self.q_network, game_manager, self.device))
+    }
+}
+
+// Juega el movimiento mas visitado por MCTS; la valoracion es la de la red
+pub struct MctsPlayer {
+    network: NetworkPlayer,
+    config: MctsConfig,
+}
+
+impl Player for MctsPlayer {
+    fn nombre(&self) -> String {
+        format!("{} + MCTS ({} simulaciones)", self.network.ruta, self.config.simulations)
+    }
+
+    fn choose_move(&mut self, game_manager: &GameManager) -> ChessMove {
+        mcts::search(&self.network.q_network, self.network.device, game_manager, &self.config).best_move
+    }
+
+    fn value(&mut self, game_manager: &GameManager) -> Option<f64> {
+        self.network.value(game_manager)
+    }
+}
+
+// Juega el resultado de una busqueda alpha-beta; los hooks deciden la evaluacion y el orden
+pub struct SearchPlayer<H: SearchHooks> {
+    nombre: String,
+    engine: Engine<H>,
+    limits: SearchLimits,
+    // Puntuacion de la ultima busqueda, desde el punto de vista de las blancas
+    ultimo_valor: Option<f64>,
+}
+
+impl<H: SearchHooks> SearchPlayer<H> {
+    pub fn new(nombre: String, hooks: H, limits: SearchLimits) -> SearchPlayer<H> {
+        SearchPlayer { nombre, engine: Engine::new(hooks, TT_BITS), limits, ultimo_valor: None }
+    }
+}
+
+impl<H: SearchHooks> Player for SearchPlayer<H> {
+    fn nombre(&self) -> String {
+        self.nombre.clone()
+    }
+
+    fn choose_move(&mut self, game_manager: &GameManager) -> ChessMove {
+        let board = game_manager.board();
+        let result = self.engine.search(&board, game_manager.previous_positions(), game_manager.num_moves(), &self.limits);
+
+        let valor = result.score as f64 / MAX_REWARD as f64;
+        self.ultimo_valor = Some(match board.side_to_move() {
+            Color::White => valor,
+            Color::Black => -valor,
+        });
+
+        result.best_move.unwrap()
+    }
+
+    fn value(&mut self, _game_manager: &GameManager) -> Option<f64> {
+        self.ultimo_valor
+    }
+}
+
+pub fn greedy_move(q_network: &DQNModelNN, game_manager: &GameManager, device: Device) -> ChessMove {
+    let mask = legal_mask(&game_manager.legal_actions()).to_device(device);
+
+    let action = no_grad(|| {
+        let qv = q_network.forward_t(&board_to_tensor(&game_manager.packed_state(), q_network.encoding()).to_device(device), false);
+        mask_illegal(&qv.view([-1]), &mask).argmax(None, false).int64_value(&[]) as usize
+    });
+
+    // La mascara garantiza que la accion elegida es legal
+    decode_move(&game_manager.board(), action).unwrap()
+}
+
+// Valor de la red desde el punto de vista de las blancas
+pub fn network_value(q_network: &DQNModelNN, game_manager: &GameManager, device: Device) -> f64 {
+    let state = game_manager.packed_state();
+    let legal_actions = game_manager.legal_actions();
+
+    // Sin movimientos no hay maximo legal: mate o ahogado
+    let valor = if legal_actions.is_empty() {
+        if *game_manager.board().checkers() != EMPTY { -1.0 } else { 0.0 }
+    }
+    else {
+        let mask = legal_mask(&legal_actions);
+        no_grad(|| q_network.state_value(&board_to_tensor(&state, q_network.encoding()).to_device(device), &mask))
+            .double_value(&[0])
+    };
+
+    if state.side_to_move == 0 { valor } else { -valor }
+}
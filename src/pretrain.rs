@@ -0,0 +1,305 @@
+use std::{path::Path, process::exit};
+
+use chess::{Color, GameResult};
+use clap::ValueEnum;
+use rand::{seq::SliceRandom, SeedableRng};
+use rand_chacha::ChaCha8Rng;
+use tch::{
+    nn::{self, ModuleT},
+    Device, Tensor,
+};
+
+use crate::{
+    adam::Adam,
+    dqn_nn_model::DQNModelNN,
+    dqn_train::{create_buffer, save_buffer},
+    evaluator::evaluate_relative,
+    game_manager::GameManager,
+    move_encoding::encode_move,
+    n_step::GameTransitions,
+    pgn::{load_pgn, parse_san, PgnGame},
+    replay_buffer::BufferElement,
+    state_encoding::{batch_to_tensor, PackedState},
+    train_config::{TargetMode, TrainConfig},
+};
+
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PretrainMode {
+    // Ajusta los Q-values de los movimientos jugados al resultado de la partida
+    Fit,
+    // Anade las transiciones al replay buffer para el self-play
+    Seed,
+    Both,
+}
+
+// Transicion de una partida importada y bando que movio
+type Jugada = (BufferElement, Color);
+
+pub fn pretrain(
+    config: &TrainConfig,
+    rutas_pgn: &[String],
+    mode: PretrainMode,
+    epochs: u32,
+    max_games: Option<usize>,
+    device: Device,
+) {
+    let mut partidas: Vec<Vec<Jugada>> = Vec::new();
+    let mut descartadas = 0;
+
+    'ficheros: for ruta in rutas_pgn {
+        let juegos = match load_pgn(ruta) {
+            Ok(juegos) => juegos,
+            Err(e) => {
+                println!("{e}");
+                exit(1);
+            }
+        };
+
+        for juego in juegos.iter() {
+            if max_games.is_some_and(|max| partidas.len() >= max) {
+                break 'ficheros;
+            }
+
+            match game_transitions(juego) {
+                Ok(jugadas) => partidas.push(jugadas),
+                Err(_) => descartadas += 1,
+            }
+        }
+    }
+
+    let num_jugadas: usize = partidas.iter().map(|p| p.len()).sum();
+    println!("Partidas importadas: {} ({num_jugadas} movimientos), descartadas: {descartadas}", partidas.len());
+    if partidas.is_empty() {
+        return;
+    }
+
+    if mode != PretrainMode::Seed {
+        fit(config, &partidas, epochs, device);
+    }
+
+    if mode != PretrainMode::Fit {
+        seed_buffer(config, partidas);
+    }
+}
+
+// Repite la partida en GameManager con las mismas recompensas que el self-play: evaluate tras cada
+// movimiento para el bando que mueve. Si la partida acaba sin mate ni tablas en el tablero,
+// el ultimo movimiento lleva el resultado del PGN como abandono o tablas acordadas.
+fn game_transitions(juego: &PgnGame) -> Result<Vec<Jugada>, String> {
+    let resultado_final = match juego.result.as_str() {
+        "1-0" => GameResult::BlackResigns,
+        "0-1" => GameResult::WhiteResigns,
+        "1/2-1/2" => GameResult::DrawAccepted,
+        _ => return Err("Partida sin resultado".to_owned()),
+    };
+    let mut game_manager = match juego.tag("FEN") {
+        Some(fen) => GameManager::from_fen(fen)?,
+        None => GameManager::new(),
+    };
+    let mut jugadas = Vec::with_capacity(juego.moves.len());
+
+    for (i, san) in juego.moves.iter().enumerate() {
+        let board = game_manager.board();
+        let movimiento = parse_san(&board, san).ok_or(format!("Movimiento invalido: {san}"))?;
+        let actual_state = game_manager.packed_state();
+
+        game_manager.do_move(movimiento);
+        game_manager.declare_draw();
+
+        let mover = board.side_to_move();
+        let game_result = match game_manager.result() {
+            None if i + 1 == juego.moves.len() => Some(resultado_final),
+            resultado => resultado,
+        };
+        let done = game_result.is_some();
+        let reward = evaluate_relative(&game_manager.board(), i as u32, game_result, mover);
+
+        jugadas.push((
+            BufferElement {
+                actual_state,
+                action: encode_move(movimiento),
+                reward,
+                next_state: game_manager.packed_state(),
+                next_legal_actions: if done { Vec::new() } else { game_manager.legal_actions() },
+                done,
+                next_value: None,
+            },
+            mover,
+        ));
+
+        if done {
+            break;
+        }
+    }
+
+    Ok(jugadas)
+}
+
+// Regresion de Q(s, a) al retorno completo de cada jugada segun el target mode
+fn fit(config: &TrainConfig, partidas: &[Vec<Jugada>], epochs: u32, device: Device) {
+    let mut ejemplos: Vec<(PackedState, i64, f32)> = Vec::new();
+    for jugadas in partidas {
+        let retornos = game_returns(jugadas, config.gamma as f32, config.target_mode);
+        for ((jugada, _), retorno) in jugadas.iter().zip(retornos) {
+            ejemplos.push((jugada.actual_state, jugada.action as i64, retorno));
+        }
+    }
+
+    if let Err(e) = config.save(config.dump_path()) {
+        println!("{e}");
+    }
+
+    let mut vs = nn::VarStore::new(device);
+    let q_network = DQNModelNN::new(&vs.root(), &config.model);
+    if Path::new(&config.model_path).exists() {
+        if let Err(e) = vs.load(&config.model_path) {
+            println!("No se pudo cargar {}: {e}", config.model_path);
+            exit(1);
+        }
+    }
+
+    let mut optimizador = Adam::new(&vs, config.learning_rate, config.weight_decay);
+    let mut rng = match config.seed {
+        Some(seed) => ChaCha8Rng::seed_from_u64(seed),
+        None => ChaCha8Rng::from_entropy(),
+    };
+    let mut indices: Vec<usize> = (0..ejemplos.len()).collect();
+
+    for epoch in 0..epochs {
+        indices.shuffle(&mut rng);
+        let mut perdida_total = 0.0;
+        let mut lotes = 0;
+
+        // Batch norm necesita al menos dos ejemplos por lote
+        for lote in indices.chunks(config.batch_size).filter(|lote| lote.len() > 1) {
+            let estados: Vec<&PackedState> = lote.iter().map(|i| &ejemplos[*i].0).collect();
+            let acciones: Vec<i64> = lote.iter().map(|i| ejemplos[*i].1).collect();
+            let retornos: Vec<f32> = lote.iter().map(|i| ejemplos[*i].2).collect();
+
+            let estados_tensor = batch_to_tensor(&estados, config.model.encoding).to_device(device);
+            let acciones_tensor = Tensor::from_slice(acciones.as_slice()).to_device(device);
+            let retornos_tensor = Tensor::from_slice(retornos.as_slice()).to_device(device);
+
+            let q_values_action =
+                q_network.forward_t(&estados_tensor, true).gather(1, &acciones_tensor.unsqueeze(-1), false).squeeze_dim(1);
+            let loss = q_values_action.smooth_l1_loss(&retornos_tensor, tch::Reduction::Mean, 1.0);
+
+            perdida_total += loss.double_value(&[]);
+            lotes += 1;
+            optimizador.backward_step(&loss);
+        }
+
+        println!("Epoca {}/{epochs}: loss {:.6}", epoch + 1, perdida_total / lotes.max(1) as f64);
+        vs.save(&config.model_path).unwrap();
+    }
+}
+
+// Retornos hasta el final de la partida con el mismo punto fijo que el TD target:
+// standard r + gamma G', negamax r - gamma G' y en opponent_reply cada bando encadena sus propias jugadas,
+// con la recompensa de la respuesta del rival cambiada de signo (como en GameTransitions)
+fn game_returns(jugadas: &[Jugada], gamma: f32, target_mode: TargetMode) -> Vec<f32> {
+    let n = jugadas.len();
+    let mut retornos = vec![0.0; n];
+
+    for t in (0..n).rev() {
+        let reward = jugadas[t].0.reward;
+        let siguiente = retornos.get(t + 1).copied().unwrap_or(0.0);
+
+        let retorno = match target_mode {
+            TargetMode::Standard => reward + gamma * siguiente,
+            TargetMode::Negamax => reward - gamma * siguiente,
+            TargetMode::OpponentReply if t + 1 == n => reward,
+            TargetMode::OpponentReply => -jugadas[t + 1].0.reward + gamma * retornos.get(t + 2).copied().unwrap_or(0.0),
+        };
+        retornos[t] = retorno;
+    }
+
+    retornos
+}
+
+// Las transiciones pasan por el mismo n-step y target mode que en self-play
+fn seed_buffer(config: &TrainConfig, partidas: Vec<Vec<Jugada>>) {
+    let mut buffer = create_buffer(config);
+
+    for jugadas in partidas {
+        let mut transiciones = GameTransitions::new(config);
+        for (jugada, mover) in jugadas {
+            for t in transiciones.push(jugada, mover) {
+                buffer.add(t);
+            }
+        }
+    }
+
+    let ruta = Path::new(&config.replay.buffer_path);
+    save_buffer(buffer.as_ref(), ruta);
+    println!("Replay buffer con {} transiciones guardado en {}", buffer.len(), ruta.display());
+}
+
+#[cfg(test)]
+mod tests {
+    use chess::Board;
+
+    use super::*;
+    use crate::{evaluator::evaluate, test_utils::transition};
+
+    fn partida(moves: &str, result: &str) -> PgnGame {
+        PgnGame { tags: Vec::new(), moves: moves.split_whitespace().map(str::to_owned).collect(), result: result.to_owned() }
+    }
+
+    #[test]
+    fn returns_follow_target_mode() {
+        let jugadas: Vec<Jugada> = [0.1, 0.2, 1.0]
+            .iter()
+            .enumerate()
+            .map(|(i, r)| (transition(*r, i as u16, i == 2), if i % 2 == 0 { Color::White } else { Color::Black }))
+            .collect();
+
+        let casos = [
+            (TargetMode::Standard, [0.1 + 0.5 * (0.2 + 0.5 * 1.0), 0.2 + 0.5 * 1.0, 1.0]),
+            (TargetMode::Negamax, [0.1 - 0.5 * (0.2 - 0.5 * 1.0), 0.2 - 0.5 * 1.0, 1.0]),
+            (TargetMode::OpponentReply, [-0.2 + 0.5 * 1.0, -1.0, 1.0]),
+        ];
+        for (target_mode, esperados) in casos {
+            let retornos = game_returns(&jugadas, 0.5, target_mode);
+            for (retorno, esperado) in retornos.iter().zip(esperados) {
+                assert!((retorno - esperado).abs() < 1e-6, "{target_mode:?}: {retornos:?} != {esperados:?}");
+            }
+        }
+    }
+
+    #[test]
+    fn pgn_rewards_match_self_play() {
+        let jugadas = game_transitions(&partida("f3 e5 g4 Qh4#", "0-1")).unwrap();
+        assert_eq!(jugadas.len(), 4);
+
+        // Recompensa densa de evaluate tras cada movimiento, para el bando que mueve
+        let mut board = Board::default();
+        for (i, ((jugada, mover), san)) in jugadas.iter().zip(["f3", "e5", "g4"]).enumerate() {
+            board = board.make_move_new(parse_san(&board, san).unwrap());
+            assert_eq!(jugada.reward, evaluate_relative(&board, i as u32, None, *mover));
+            assert!(!jugada.done);
+        }
+
+        // El mate lleva la recompensa maxima
+        let (mate, mover) = &jugadas[3];
+        assert_eq!(*mover, Color::Black);
+        assert_eq!(mate.reward, 1.0);
+        assert!(mate.done && mate.next_legal_actions.is_empty());
+    }
+
+    #[test]
+    fn pgn_result_scores_last_move() {
+        // Las blancas ganan por abandono tras 1. e4 e5: el ultimo movimiento es de las negras
+        let jugadas = game_transitions(&partida("e4 e5", "1-0")).unwrap();
+        let (ultima, mover) = &jugadas[1];
+        assert_eq!(*mover, Color::Black);
+        assert_eq!(ultima.reward, -1.0);
+        assert!(ultima.done);
+
+        let jugadas = game_transitions(&partida("e4 e5", "1/2-1/2")).unwrap();
+        assert_eq!(jugadas[1].0.reward, 0.0);
+        assert_eq!(jugadas[0].0.reward, evaluate(&Board::default().make_move_new(parse_san(&Board::default(), "e4").unwrap()), 0, None));
+
+        assert!(game_transitions(&partida("e4 e5", "*")).is_err());
+    }
+}
@@ -0,0 +1,192 @@
+use std::{
+    fs::File,
+    io::{self, BufReader, BufWriter, Write},
+    path::Path,
+};
+
+use rand::{Rng, RngCore};
+
+use crate::{
+    binary_io::{read_f64, write_f64},
+    replay_buffer::{read_element, read_header, write_element, write_header, BufferElement, BufferHeader, Replay, Samples, KIND_PRIORITIZED},
+    sum_tree::SumTree,
+    train_config::ReplayConfig,
+};
+
+// Prioritized experience replay (Schaul et al.): P(i) = p_i^alpha / sum_k p_k^alpha
+pub struct PrioritizedReplayBuffer {
+    buffer: Vec<BufferElement>,
+    capacity: usize,
+    index: usize,
+    tree: SumTree,
+    max_priority: f64,
+    alpha: f64,
+    beta: f64,
+    beta_increment: f64,
+    epsilon: f64,
+}
+
+impl PrioritizedReplayBuffer {
+    pub fn new(capacity: usize, config: &ReplayConfig) -> PrioritizedReplayBuffer {
+        PrioritizedReplayBuffer {
+            buffer: Vec::with_capacity(capacity),
+            capacity,
+            index: 0,
+            tree: SumTree::new(capacity),
+            max_priority: 1.0,
+            alpha: config.alpha,
+            beta: config.beta_start,
+            beta_increment: (1.0 - config.beta_start) / config.beta_steps.max(1) as f64,
+            epsilon: config.priority_epsilon,
+        }
+    }
+
+    // alpha y epsilon se toman de la configuracion actual; beta y las prioridades del fichero
+    pub fn load(ruta: &Path, config: &ReplayConfig) -> io::Result<PrioritizedReplayBuffer> {
+        let mut r = BufReader::new(File::open(ruta)?);
+        let header = read_header(&mut r, KIND_PRIORITIZED)?;
+
+        let mut res = PrioritizedReplayBuffer::new(header.capacity, config);
+        res.index = header.index;
+        res.max_priority = read_f64(&mut r)?;
+        res.beta = read_f64(&mut r)?;
+
+        for _ in 0..header.len {
+            res.buffer.push(read_element(&mut r)?);
+        }
+        for i in 0..header.len {
+            res.tree.set(i, read_f64(&mut r)?);
+        }
+
+        Ok(res)
+    }
+}
+
+impl Replay for PrioritizedReplayBuffer {
+    fn add(&mut self, element: BufferElement) {
+        if self.index >= self.buffer.len() {
+            self.buffer.push(element);
+        }
+        else {
+            self.buffer[self.index] = element;
+        }
+
+        // Las transiciones nuevas entran con la prioridad maxima para muestrearse al menos una vez
+        self.tree.set(self.index, self.max_priority.powf(self.alpha));
+
+        self.index += 1;
+
+        if self.index == self.capacity {
+            self.index = 0;
+        }
+    }
+
+    fn get_samples(&mut self, samples: usize, rng: &mut dyn RngCore) -> Samples {
+        let total = self.tree.total();
+        let segment = total / samples as f64;
+        let len = self.buffer.len();
+
+        let mut indices = Vec::with_capacity(samples);
+        let mut weights = Vec::with_capacity(samples);
+
+        // Muestreo estratificado: una muestra por segmento de la masa total
+        for i in 0..samples {
+            let value = rng.gen_range(segment * i as f64..segment * (i + 1) as f64);
+            let index = self.tree.find(value).min(len - 1);
+            let probability = self.tree.get(index) / total;
+
+            indices.push(index);
+            weights.push((len as f64 * probability).powf(-self.beta));
+        }
+
+        let max_weight = weights.iter().cloned().fold(f64::MIN, f64::max);
+        self.beta = f64::min(1.0, self.beta + self.beta_increment);
+
+        Samples {
+            elements: indices.iter().map(|i| self.buffer[*i].clone()).collect(),
+            indices,
+            weights: weights.iter().map(|w| (w / max_weight) as f32).collect(),
+        }
+    }
+
+    fn update_priorities(&mut self, indices: &[usize], td_errors: &[f32]) {
+        for (index, td_error) in indices.iter().zip(td_errors) {
+            let priority = td_error.abs() as f64 + self.epsilon;
+            self.max_priority = f64::max(self.max_priority, priority);
+            self.tree.set(*index, priority.powf(self.alpha));
+        }
+    }
+
+    fn len(&self) -> usize {
+        self.buffer.len()
+    }
+
+    fn save(&self, ruta: &Path) -> io::Result<()> {
+        let mut w = BufWriter::new(File::create(ruta)?);
+        write_header(&mut w, &BufferHeader {
+            kind: KIND_PRIORITIZED,
+            capacity: self.capacity,
+            index: self.index,
+            len: self.buffer.len(),
+        })?;
+        write_f64(&mut w, self.max_priority)?;
+        write_f64(&mut w, self.beta)?;
+
+        for element in self.buffer.iter() {
+            write_element(&mut w, element)?;
+        }
+        for i in 0..self.buffer.len() {
+            write_f64(&mut w, self.tree.get(i))?;
+        }
+
+        w.flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::{temp_path, transition};
+
+    #[test]
+    fn ring_overwrite_replaces_old_priority() {
+        let config = ReplayConfig { alpha: 1.0, priority_epsilon: 0.0, ..Default::default() };
+        let mut buffer = PrioritizedReplayBuffer::new(3, &config);
+        for i in 0..3 {
+            buffer.add(transition(i as f32, i, false));
+        }
+        assert_eq!(buffer.tree.total(), 3.0);
+
+        buffer.update_priorities(&[0, 1, 2], &[0.0, -2.0, 0.5]);
+        assert_eq!(buffer.tree.total(), 2.5);
+
+        // La cuarta transicion sobrescribe la 0 y entra con la prioridad maxima vista
+        buffer.add(transition(3.0, 3, false));
+        assert_eq!(buffer.len(), 3);
+        assert_eq!(buffer.buffer[0].reward, 3.0);
+        assert_eq!(buffer.tree.get(0), 2.0);
+        assert_eq!(buffer.tree.total(), 4.5);
+    }
+
+    #[test]
+    fn save_load_keeps_priorities() {
+        let config = ReplayConfig::default();
+        let mut buffer = PrioritizedReplayBuffer::new(4, &config);
+        for i in 0..3 {
+            buffer.add(transition(i as f32, i, false));
+        }
+        buffer.update_priorities(&[0, 2], &[3.0, 0.25]);
+
+        let ruta = temp_path("prioritized_roundtrip.bin");
+        buffer.save(&ruta).unwrap();
+        let cargado = PrioritizedReplayBuffer::load(&ruta, &config).unwrap();
+        std::fs::remove_file(&ruta).unwrap();
+
+        assert_eq!(cargado.len(), 3);
+        assert_eq!((cargado.index, cargado.max_priority, cargado.beta), (buffer.index, buffer.max_priority, buffer.beta));
+        for i in 0..4 {
+            assert_eq!(cargado.tree.get(i), buffer.tree.get(i));
+        }
+        assert_eq!(cargado.tree.total(), buffer.tree.total());
+    }
+}
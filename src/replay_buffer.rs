@@ -1,12 +1,131 @@
-use rand::seq::SliceRandom;
+use std::{
+    fs::File,
+    io::{self, BufReader, BufWriter, Read, Write},
+    path::Path,
+};
+
+use rand::{seq::index, RngCore};
+
+use crate::{
+    binary_io::{invalid_data, read_f32, read_u16, read_u32, read_u64, read_u8, write_f32, write_u16, write_u32, write_u64, write_u8},
+    state_encoding::PackedState,
+};
 
 #[derive(Clone)]
 pub struct BufferElement {
-    pub actual_state: String,
+    pub actual_state: PackedState,
     pub action: usize,
     pub reward: f32,
-    pub next_state: String,
-    pub done: bool
+    pub next_state: PackedState,
+    pub next_legal_actions: Vec<u16>,
+    pub done: bool,
+    // Valor MCTS de next_state para el bando que mueve en el; solo en movimientos de MCTS en self-play
+    pub next_value: Option<f32>,
+}
+
+pub struct Samples {
+    pub elements: Vec<BufferElement>,
+    pub indices: Vec<usize>,
+    // Pesos de importance sampling para la perdida (1.0 en muestreo uniforme)
+    pub weights: Vec<f32>,
+}
+
+pub trait Replay {
+    fn add(&mut self, element: BufferElement);
+    // El generador lo aporta el entrenamiento para que el muestreo sea reproducible
+    fn get_samples(&mut self, samples: usize, rng: &mut dyn RngCore) -> Samples;
+    fn update_priorities(&mut self, indices: &[usize], td_errors: &[f32]);
+    fn len(&self) -> usize;
+    fn save(&self, ruta: &Path) -> io::Result<()>;
+}
+
+// Formato del fichero: MAGIC, version, tipo de buffer, capacidad, indice del anillo,
+// numero de elementos y despues los elementos (y datos propios de cada tipo de buffer)
+const MAGIC: &[u8; 4] = b"DQRB";
+const VERSION: u32 = 3;
+
+pub const KIND_UNIFORM: u8 = 0;
+pub const KIND_PRIORITIZED: u8 = 1;
+
+pub struct BufferHeader {
+    pub kind: u8,
+    pub capacity: usize,
+    pub index: usize,
+    pub len: usize,
+}
+
+pub fn write_header<W: Write>(w: &mut W, header: &BufferHeader) -> io::Result<()> {
+    w.write_all(MAGIC)?;
+    write_u32(w, VERSION)?;
+    write_u8(w, header.kind)?;
+    write_u64(w, header.capacity as u64)?;
+    write_u64(w, header.index as u64)?;
+    write_u64(w, header.len as u64)
+}
+
+pub fn read_header<R: Read>(r: &mut R, kind: u8) -> io::Result<BufferHeader> {
+    let mut magic = [0u8; 4];
+    r.read_exact(&mut magic)?;
+    if &magic != MAGIC {
+        return Err(invalid_data("no es un fichero de replay buffer"));
+    }
+
+    let version = read_u32(r)?;
+    if version != VERSION {
+        return Err(invalid_data(&format!("version de replay buffer no soportada: {version}")));
+    }
+
+    let header = BufferHeader {
+        kind: read_u8(r)?,
+        capacity: read_u64(r)? as usize,
+        index: read_u64(r)? as usize,
+        len: read_u64(r)? as usize,
+    };
+
+    if header.kind != kind {
+        return Err(invalid_data("el fichero contiene otro tipo de replay buffer"));
+    }
+    if header.len > header.capacity || header.index >= header.capacity.max(1) {
+        return Err(invalid_data("cabecera de replay buffer corrupta"));
+    }
+
+    Ok(header)
+}
+
+pub fn write_element<W: Write>(w: &mut W, element: &BufferElement) -> io::Result<()> {
+    element.actual_state.write_to(w)?;
+    write_u32(w, element.action as u32)?;
+    write_f32(w, element.reward)?;
+    element.next_state.write_to(w)?;
+    write_u8(w, element.done as u8)?;
+    write_u16(w, element.next_legal_actions.len() as u16)?;
+    for action in element.next_legal_actions.iter() {
+        write_u16(w, *action)?;
+    }
+    write_u8(w, element.next_value.is_some() as u8)?;
+    write_f32(w, element.next_value.unwrap_or(0.0))?;
+
+    Ok(())
+}
+
+pub fn read_element<R: Read>(r: &mut R) -> io::Result<BufferElement> {
+    let actual_state = PackedState::read_from(r)?;
+    let action = read_u32(r)? as usize;
+    let reward = read_f32(r)?;
+    let next_state = PackedState::read_from(r)?;
+    let done = read_u8(r)? != 0;
+
+    let num_legal = read_u16(r)? as usize;
+    let mut next_legal_actions = Vec::with_capacity(num_legal);
+    for _ in 0..num_legal {
+        next_legal_actions.push(read_u16(r)?);
+    }
+
+    let tiene_valor = read_u8(r)? != 0;
+    let valor = read_f32(r)?;
+    let next_value = tiene_valor.then_some(valor);
+
+    Ok(BufferElement { actual_state, action, reward, next_state, next_legal_actions, done, next_value })
 }
 
 pub struct ReplayBuffer {
@@ -20,7 +139,21 @@ impl ReplayBuffer{
         ReplayBuffer { buffer: Vec::with_capacity(capacity), capacity, index: 0}
     }
 
-    pub fn add(&mut self, element: BufferElement) {
+    pub fn load(ruta: &Path) -> io::Result<ReplayBuffer> {
+        let mut r = BufReader::new(File::open(ruta)?);
+        let header = read_header(&mut r, KIND_UNIFORM)?;
+
+        let mut buffer = Vec::with_capacity(header.capacity);
+        for _ in 0..header.len {
+            buffer.push(read_element(&mut r)?);
+        }
+
+        Ok(ReplayBuffer { buffer, capacity: header.capacity, index: header.index })
+    }
+}
+
+impl Replay for ReplayBuffer {
+    fn add(&mut self, element: BufferElement) {
         if self.index >= self.buffer.len() {
             self.buffer.insert(self.index, element)
         }
@@ -35,8 +168,104 @@ impl ReplayBuffer{
         }
     }
 
-    pub fn get_samples(&self, samples: usize) -> Vec<BufferElement> {
-        let mut rng = rand::thread_rng();
-        self.buffer.choose_multiple(&mut rng, samples).cloned().collect()
+    fn get_samples(&mut self, samples: usize, rng: &mut dyn RngCore) -> Samples {
+        let indices = index::sample(rng, self.buffer.len(), samples.min(self.buffer.len())).into_vec();
+
+        Samples {
+            elements: indices.iter().map(|i| self.buffer[*i].clone()).collect(),
+            weights: vec![1.0; indices.len()],
+            indices,
+        }
+    }
+
+    fn update_priorities(&mut self, _indices: &[usize], _td_errors: &[f32]) {}
+
+    fn len(&self) -> usize {
+        self.buffer.len()
+    }
+
+    fn save(&self, ruta: &Path) -> io::Result<()> {
+        let mut w = BufWriter::new(File::create(ruta)?);
+        write_header(&mut w, &BufferHeader {
+            kind: KIND_UNIFORM,
+            capacity: self.capacity,
+            index: self.index,
+            len: self.buffer.len(),
+        })?;
+
+        for element in self.buffer.iter() {
+            write_element(&mut w, element)?;
+        }
+
+        w.flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{fs, str::FromStr};
+
+    use chess::Board;
+
+    use super::*;
+    use crate::test_utils::{temp_path, transition};
+
+    fn header_bytes() -> Vec<u8> {
+        let mut bytes = Vec::new();
+        write_header(&mut bytes, &BufferHeader { kind: KIND_UNIFORM, capacity: 4, index: 1, len: 2 }).unwrap();
+        bytes
+    }
+
+    #[test]
+    fn save_load_roundtrip() {
+        let board = Board::from_str("r3k2r/8/8/3pP3/8/8/8/R3K2R w KQkq d6 0 1").unwrap();
+        let mut buffer = ReplayBuffer::new(3);
+
+        // Cuatro transiciones en un anillo de tres: el indice da la vuelta
+        for i in 0..4 {
+            let mut t = transition(i as f32 * 0.5, i, i == 3);
+            t.next_state = PackedState::from_board(&board).with_history(i as u32, 1);
+            t.next_legal_actions = (0..i * 10).collect();
+            t.next_value = (i % 2 == 1).then_some(-0.25 * i as f32);
+            buffer.add(t);
+        }
+
+        let ruta = temp_path("replay_roundtrip.bin");
+        buffer.save(&ruta).unwrap();
+        let cargado = ReplayBuffer::load(&ruta).unwrap();
+        fs::remove_file(&ruta).unwrap();
+
+        assert_eq!((cargado.capacity, cargado.index), (buffer.capacity, buffer.index));
+        assert_eq!(cargado.len(), 3);
+        for (a, b) in cargado.buffer.iter().zip(buffer.buffer.iter()) {
+            assert_eq!(a.actual_state, b.actual_state);
+            assert_eq!(a.action, b.action);
+            assert_eq!(a.reward, b.reward);
+            assert_eq!(a.next_state, b.next_state);
+            assert_eq!(a.next_legal_actions, b.next_legal_actions);
+            assert_eq!(a.done, b.done);
+            assert_eq!(a.next_value, b.next_value);
+        }
+    }
+
+    #[test]
+    fn header_roundtrip() {
+        let header = read_header(&mut header_bytes().as_slice(), KIND_UNIFORM).unwrap();
+        assert_eq!((header.kind, header.capacity, header.index, header.len), (KIND_UNIFORM, 4, 1, 2));
+    }
+
+    #[test]
+    fn rejects_bad_magic_version_and_kind() {
+        let mut bytes = header_bytes();
+        bytes[0] = b'X';
+        assert_eq!(read_header(&mut bytes.as_slice(), KIND_UNIFORM).err().unwrap().kind(), io::ErrorKind::InvalidData);
+
+        let mut bytes = header_bytes();
+        bytes[4..8].copy_from_slice(&(VERSION + 1).to_le_bytes());
+        let error = read_header(&mut bytes.as_slice(), KIND_UNIFORM).err().unwrap();
+        assert!(error.to_string().contains("version"), "{error}");
+
+        assert!(read_header(&mut header_bytes().as_slice(), KIND_PRIORITIZED).is_err());
+        assert!(read_header(&mut &header_bytes()[..10], KIND_UNIFORM).is_err());
     }
-}
\ No newline at end of file
+}
@@ -0,0 +1,429 @@
+use chess::{Board, ChessMove, Color, MoveGen, Piece, EMPTY};
+
+use crate::evaluator::{evaluate, MAX_REWARD};
+
+// Puntuaciones en la escala de evaluate() * MAX_REWARD (centipeones); los mates quedan por encima
+pub const MATE: i32 = 1_000_000;
+const MATE_BOUND: i32 = MATE - 1000;
+const INFINITO: i32 = MATE + 1;
+
+// Profundidad maxima de la quiescence, por si una cadena de jaques y capturas no termina
+const MAX_PLY: u32 = 64;
+
+// Las capturas y la jugada de la tabla van siempre por delante de los movimientos tranquilos
+const CAPTURE_ORDER: i32 = 1_000_000;
+
+const PIECE_ORDER_VALUE: [i32; 6] = [100, 320, 350, 500, 900, 0];
+
+// Puntos de extension del motor: evaluacion de las hojas y orden de los movimientos tranquilos
+pub trait SearchHooks {
+    // Valor de la posicion para el bando que mueve; ply es la distancia a la raiz, para puntuar los mates como MATE - ply
+    fn evaluate(&mut self, board: &Board, num_moves: u32, _ply: u32) -> i32 {
+        let valor = (evaluate(board, num_moves, None) * MAX_REWARD) as i32;
+        match board.side_to_move() {
+            Color::White => valor,
+            Color::Black => -valor,
+        }
+    }
+
+    // Prioridad de cada movimiento (mayor primero, en (-CAPTURE_ORDER, CAPTURE_ORDER));
+    // None deja los movimientos tranquilos en el orden del generador
+    fn order_moves(&mut self, _board: &Board, _moves: &[ChessMove]) -> Option<Vec<i32>> {
+        None
+    }
+}
+
+// Motor clasico: solo evaluator::evaluate y MVV-LVA
+pub struct ClassicalHooks;
+
+impl SearchHooks for ClassicalHooks {}
+
+#[derive(Debug, Clone, Copy)]
+pub struct SearchLimits {
+    pub depth: u32,
+    // Nodos maximos por busqueda; al agotarse se usa la ultima iteracion completa
+    pub nodes: Option<u64>,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct SearchResult {
+    pub best_move: Option<ChessMove>,
+    // Para el bando que mueve
+    pub score: i32,
+    pub depth: u32,
+    pub nodes: u64,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Bound {
+    Exact,
+    Lower,
+    Upper,
+}
+
+#[derive(Clone, Copy)]
+struct TtEntry {
+    key: u64,
+    depth: u32,
+    score: i32,
+    bound: Bound,
+    best_move: Option<ChessMove>,
+}
+
+// Negamax alpha-beta con iterative deepening, quiescence y tabla de transposicion indexada por Board::get_hash
+pub struct Engine<H: SearchHooks> {
+    hooks: H,
+    tt: Vec<Option<TtEntry>>,
+    nodes: u64,
+    max_nodes: Option<u64>,
+    abortado: bool,
+    // Hashes de la partida y de la rama actual para detectar repeticiones
+    historial: Vec<u64>,
+    num_moves: u32,
+    mejor_raiz: Option<ChessMove>,
+}
+
+impl<H: SearchHooks> Engine<H> {
+    // La tabla tiene 2^tt_bits entradas
+    pub fn new(hooks: H, tt_bits: u32) -> Engine<H> {
+        Engine {
+            hooks,
+            tt: vec![None; 1 << tt_bits],
+            nodes: 0,
+            max_nodes: None,
+            abortado: false,
+            historial: Vec::new(),
+            num_moves: 0,
+            mejor_raiz: None,
+        }
+    }
+
+    // previas: posiciones anteriores de la partida que cuentan para la repeticion
+    pub fn search(&mut self, board: &Board, previas: &[u64], num_moves: u32, limits: &SearchLimits) -> SearchResult {
+        self.nodes = 0;
+        self.max_nodes = limits.nodes;
+        self.abortado = false;
+        self.historial = previas.to_vec();
+        self.num_moves = num_moves;
+
+        let mut result = SearchResult { best_move: None, score: 0, depth: 0, nodes: 0 };
+
+        for depth in 1..=limits.depth.max(1) {
+            self.mejor_raiz = None;
+            let score = self.negamax(board, depth, 0, -INFINITO, INFINITO);
+
+            if self.abortado {
+                break;
+            }
+
+            result.best_move = self.mejor_raiz;
+            result.score = score;
+            result.depth = depth;
+
+            // Con un mate encontrado no hace falta seguir profundizando
+            if score.abs() > MATE_BOUND {
+                break;
+            }
+        }
+
+        // Si el presupuesto se agota antes de completar la primera iteracion
+        if result.best_move.is_none() {
+            result.best_move = MoveGen::new_legal(board).next();
+        }
+        result.nodes = self.nodes;
+
+        result
+    }
+
+    fn visit(&mut self) -> bool {
+        self.nodes += 1;
+        if self.max_nodes.is_some_and(|max| self.nodes > max) {
+            self.abortado = true;
+        }
+
+        !self.abortado
+    }
+
+    fn negamax(&mut self, board: &Board, depth: u32, ply: u32, mut alpha: i32, beta: i32) -> i32 {
+        if !self.visit() {
+            return 0;
+        }
+
+        let hash = board.get_hash();
+        if ply > 0 && self.historial.contains(&hash) {
+            return 0;
+        }
+
+        let moves: Vec<ChessMove> = MoveGen::new_legal(board).collect();
+        if moves.is_empty() {
+            return if *board.checkers() == EMPTY { 0 } else { -MATE + ply as i32 };
+        }
+
+        if depth == 0 {
+            return self.quiescence(board, ply, alpha, beta);
+        }
+
+        let mut tt_move = None;
+        if let Some(entry) = self.tt_probe(hash) {
+            tt_move = entry.best_move;
+
+            if ply > 0 && entry.depth >= depth {
+                let score = score_from_tt(entry.score, ply);
+                let corte = match entry.bound {
+                    Bound::Exact => true,
+                    Bound::Lower => score >= beta,
+                    Bound::Upper => score <= alpha,
+                };
+                if corte {
+                    return score;
+                }
+            }
+        }
+
+        let alpha_original = alpha;
+        let mut best_score = -INFINITO;
+        let mut best_move = None;
+
+        self.historial.push(hash);
+        for m in self.order(board, moves, tt_move, true) {
+            let score = -self.negamax(&board.make_move_new(m), depth - 1, ply + 1, -beta, -alpha);
+            if self.abortado {
+                break;
+            }
+
+            if score > best_score {
+                best_score = score;
+                best_move = Some(m);
+                if ply == 0 {
+                    self.mejor_raiz = Some(m);
+                }
+            }
+            alpha = alpha.max(score);
+            if alpha >= beta {
+                break;
+            }
+        }
+        self.historial.pop();
+
+        if self.abortado {
+            return 0;
+        }
+
+        let bound = if best_score <= alpha_original {
+            Bound::Upper
+        }
+        else if best_score >= beta {
+            Bound::Lower
+        }
+        else {
+            Bound::Exact
+        };
+        self.tt_store(TtEntry { key: hash, depth, score: score_to_tt(best_score, ply), bound, best_move });
+
+        best_score
+    }
+
+    // Solo capturas (o todas las respuestas si hay jaque) hasta llegar a una posicion tranquila
+    fn quiescence(&mut self, board: &Board, ply: u32, mut alpha: i32, beta: i32) -> i32 {
+        if !self.visit() {
+            return 0;
+        }
+
+        let en_jaque = *board.checkers() != EMPTY;
+        if ply >= MAX_PLY {
+            return self.hooks.evaluate(board, self.num_moves + ply, ply);
+        }
+
+        if !en_jaque {
+            let stand_pat = self.hooks.evaluate(board, self.num_moves + ply, ply);
+            if stand_pat >= beta {
+                return stand_pat;
+            }
+            alpha = alpha.max(stand_pat);
+        }
+
+        let mut movegen = MoveGen::new_legal(board);
+        if !en_jaque {
+            movegen.set_iterator_mask(*board.color_combined(!board.side_to_move()));
+        }
+        let moves: Vec<ChessMove> = movegen.collect();
+
+        if en_jaque && moves.is_empty() {
+            return -MATE + ply as i32;
+        }
+
+        for m in self.order(board, moves, None, false) {
+            let score = -self.quiescence(&board.make_move_new(m), ply + 1, -beta, -alpha);
+            if self.abortado {
+                return 0;
+            }
+
+            if score >= beta {
+                return score;
+            }
+            alpha = alpha.max(score);
+        }
+
+        alpha
+    }
+
+    // Jugada de la tabla, capturas por MVV-LVA y despues los movimientos tranquilos segun los hooks
+    // (en la quiescence no se consultan: solo hay capturas o respuestas a un jaque)
+    fn order(&mut self, board: &Board, moves: Vec<ChessMove>, tt_move: Option<ChessMove>, con_hooks: bool) -> Vec<ChessMove> {
+        let prioridades = if con_hooks { self.hooks.order_moves(board, &moves) } else { None };
+
+        let mut puntuados: Vec<(i32, ChessMove)> = moves
+            .into_iter()
+            .enumerate()
+            .map(|(i, m)| {
+                let orden = if Some(m) == tt_move {
+                    i32::MAX
+                }
+                else if let Some(valor) = capture_order(board, m) {
+                    CAPTURE_ORDER + valor
+                }
+                else {
+                    prioridades.as_ref().map_or(0, |p| p[i])
+                };
+                (orden, m)
+            })
+            .collect();
+
+        // sort_by_key es estable: sin prioridades se mantiene el orden del generador
+        puntuados.sort_by_key(|(orden, _)| std::cmp::Reverse(*orden));
+        puntuados.into_iter().map(|(_, m)| m).collect()
+    }
+
+    fn tt_probe(&self, hash: u64) -> Option<TtEntry> {
+        let index = hash as usize & (self.tt.len() - 1);
+        self.tt[index].filter(|entry| entry.key == hash)
+    }
+
+    fn tt_store(&mut self, entry: TtEntry) {
+        let index = entry.key as usize & (self.tt.len() - 1);
+        self.tt[index] = Some(entry);
+    }
+}
+
+// Most valuable victim - least valuable attacker; promociones como capturas de la pieza ganada
+fn capture_order(board: &Board, m: ChessMove) -> Option<i32> {
+    let atacante = board.piece_on(m.get_source())?;
+
+    let victima = match board.piece_on(m.get_dest()) {
+        Some(pieza) => Some(pieza),
+        // Captura al paso: el peon cambia de columna hacia una casilla vacia
+        None if atacante == Piece::Pawn && m.get_source().get_file() != m.get_dest().get_file() => Some(Piece::Pawn),
+        None => None,
+    };
+
+    let promocion = m.get_promotion().map_or(0, |p| PIECE_ORDER_VALUE[p.to_index()]);
+
+    match victima {
+        Some(v) => Some(10 * PIECE_ORDER_VALUE[v.to_index()] - PIECE_ORDER_VALUE[atacante.to_index()] + promocion),
+        None if promocion > 0 => Some(promocion),
+        None => None,
+    }
+}
+
+// Los mates se guardan relativos al nodo para que la distancia siga siendo correcta en otra rama
+fn score_to_tt(score: i32, ply: u32) -> i32 {
+    if score > MATE_BOUND {
+        score + ply as i32
+    }
+    else if score < -MATE_BOUND {
+        score - ply as i32
+    }
+    else {
+        score
+    }
+}
+
+fn score_from_tt(score: i32, ply: u32) -> i32 {
+    if score > MATE_BOUND {
+        score - ply as i32
+    }
+    else if score < -MATE_BOUND {
+        score + ply as i32
+    }
+    else {
+        score
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use chess::Square;
+
+    use super::*;
+
+    fn engine() -> Engine<ClassicalHooks> {
+        Engine::new(ClassicalHooks, 16)
+    }
+
+    fn limits(depth: u32) -> SearchLimits {
+        SearchLimits { depth, nodes: None }
+    }
+
+    #[test]
+    fn finds_mate_in_one() {
+        // Mate del pasillo con Ta8
+        let board = Board::from_str("6k1/5ppp/8/8/8/8/8/R5K1 w - - 0 1").unwrap();
+        let result = engine().search(&board, &[], 0, &limits(2));
+
+        assert_eq!(result.best_move, Some(ChessMove::new(Square::A1, Square::A8, None)));
+        assert_eq!(result.score, MATE - 1);
+    }
+
+    #[test]
+    fn stalemate_scores_zero() {
+        let board = Board::from_str("7k/5Q2/6K1/8/8/8/8/8 b - - 0 1").unwrap();
+        let result = engine().search(&board, &[], 0, &limits(3));
+
+        assert_eq!(result.score, 0);
+        assert_eq!(result.best_move, None);
+    }
+
+    #[test]
+    fn tt_mate_scores_are_relative_to_node() {
+        // Mate en el ply 5 visto desde un nodo en el ply 2: se guarda como mate a 3 plies de ese nodo
+        let guardado = score_to_tt(MATE - 5, 2);
+        assert_eq!(guardado, MATE - 3);
+        assert_eq!(score_from_tt(guardado, 2), MATE - 5);
+        // El mismo nodo alcanzado en el ply 4 de otra busqueda queda a 7 plies del mate
+        assert_eq!(score_from_tt(guardado, 4), MATE - 7);
+        assert_eq!(score_from_tt(score_to_tt(-MATE + 6, 3), 1), -MATE + 4);
+        assert_eq!(score_from_tt(score_to_tt(250, 3), 7), 250);
+
+        // Repetir la busqueda con la tabla llena y otra profundidad no cambia la distancia al mate
+        let board = Board::from_str("6k1/5ppp/8/8/8/8/8/R5K1 w - - 0 1").unwrap();
+        let mut engine = engine();
+        engine.search(&board, &[], 0, &limits(2));
+        let result = engine.search(&board, &[], 0, &limits(4));
+        assert_eq!(result.score, MATE - 1);
+    }
+
+    #[test]
+    fn captures_ordered_by_mvv_lva() {
+        // PxD, CxT y DxP disponibles a la vez
+        let board = Board::from_str("4k3/7p/8/1r2q3/3P4/2N5/8/K6Q w - - 0 1").unwrap();
+        let moves: Vec<ChessMove> = MoveGen::new_legal(&board).collect();
+        let ordenados = engine().order(&board, moves, None, false);
+
+        assert_eq!(
+            &ordenados[..3],
+            &[
+                ChessMove::new(Square::D4, Square::E5, None),
+                ChessMove::new(Square::C3, Square::B5, None),
+                ChessMove::new(Square::H1, Square::H7, None),
+            ]
+        );
+        assert!(ordenados[3..].iter().all(|m| capture_order(&board, *m).is_none()));
+
+        // La jugada de la tabla va por delante de todas las capturas
+        let tt_move = ChessMove::new(Square::A1, Square::A2, None);
+        let moves: Vec<ChessMove> = MoveGen::new_legal(&board).collect();
+        assert_eq!(engine().order(&board, moves, Some(tt_move), false)[0], tt_move);
+    }
+}
@@ -0,0 +1,161 @@
+use std::fs;
+
+use chess::MoveGen;
+use rand::{distributions::{Distribution, WeightedIndex}, Rng};
+
+use crate::{game_manager::GameManager, train_config::StartPositionsConfig};
+
+// Posiciones de salida del self-play, elegidas al azar segun su peso
+pub struct StartPositions {
+    // None es la posicion inicial
+    fens: Vec<Option<String>>,
+    pesos: WeightedIndex<f64>,
+}
+
+impl StartPositions {
+    // None si no hay fichero de posiciones: todas las partidas empiezan desde la inicial
+    pub fn load(config: &StartPositionsConfig) -> Result<Option<StartPositions>, String> {
+        let ruta = config.path.as_str();
+        if ruta.trim().is_empty() {
+            return Ok(None);
+        }
+
+        let contenido = fs::read_to_string(ruta).map_err(|e| format!("No se pudo leer {ruta}: {e}"))?;
+        let mut fens = Vec::new();
+        let mut pesos = Vec::new();
+
+        for (num_linea, linea) in contenido.lines().enumerate() {
+            let linea = linea.trim();
+            if linea.is_empty() || linea.starts_with('#') {
+                continue;
+            }
+
+            let (fen, peso) = parse_line(linea).ok_or(format!("Linea invalida en {ruta}:{}", num_linea + 1))?;
+            let game_manager = GameManager::from_fen(&fen).map_err(|e| format!("{e} en {ruta}:{}", num_linea + 1))?;
+            if MoveGen::new_legal(&game_manager.board()).len() == 0 {
+                return Err(format!("La posicion de {ruta}:{} no tiene movimientos legales", num_linea + 1));
+            }
+
+            fens.push(Some(fen));
+            pesos.push(peso);
+        }
+
+        if config.initial_weight > 0.0 {
+            fens.push(None);
+            pesos.push(config.initial_weight);
+        }
+
+        let pesos = WeightedIndex::new(&pesos).map_err(|_| format!("{ruta} no contiene posiciones con peso positivo"))?;
+        println!("Posiciones de salida: {}", fens.len());
+
+        Ok(Some(StartPositions { fens, pesos }))
+    }
+
+    pub fn sample<R: Rng>(&self, rng: &mut R) -> GameManager {
+        match &self.fens[self.pesos.sample(rng)] {
+            // Ya se comprobo al cargar el fichero
+            Some(fen) => GameManager::from_fen(fen).unwrap(),
+            None => GameManager::new(),
+        }
+    }
+}
+
+// Una posicion por linea: FEN completo o EPD (cuatro campos y operaciones "opcode operando;").
+// La operacion "weight N;" fija el peso de la posicion; sin ella pesa 1.
+fn parse_line(linea: &str) -> Option<(String, f64)> {
+    let campos: Vec<&str> = linea.split_whitespace().collect();
+    if campos.len() < 4 {
+        return None;
+    }
+
+    let mut fen = campos[..4].join(" ");
+    let mut resto = &campos[4..];
+    if resto.len() >= 2 && resto[0].parse::<u32>().is_ok() && resto[1].parse::<u32>().is_ok() {
+        fen = format!("{fen} {} {}", resto[0], resto[1]);
+        resto = &resto[2..];
+    }
+
+    let mut peso = 1.0;
+    for operacion in resto.join(" ").split(';') {
+        let mut partes = operacion.split_whitespace();
+        if partes.next() == Some("weight") {
+            peso = partes.next()?.parse::<f64>().ok().filter(|p| *p >= 0.0)?;
+        }
+    }
+
+    Some((fen, peso))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use rand::SeedableRng;
+    use rand_chacha::ChaCha8Rng;
+
+    use super::*;
+    use crate::test_utils::temp_path;
+
+    #[test]
+    fn parse_fen_and_epd_lines() {
+        assert_eq!(
+            parse_line("4k3/8/8/8/8/8/8/R3K3 w - - 5 20"),
+            Some(("4k3/8/8/8/8/8/8/R3K3 w - - 5 20".to_owned(), 1.0))
+        );
+        assert_eq!(
+            parse_line("4k3/8/8/8/8/8/8/R3K3 w - - id \"krk\"; weight 2.5;"),
+            Some(("4k3/8/8/8/8/8/8/R3K3 w - -".to_owned(), 2.5))
+        );
+        assert_eq!(
+            parse_line("4k3/8/8/8/8/8/8/R3K3 b - - 0 1 weight 0;"),
+            Some(("4k3/8/8/8/8/8/8/R3K3 b - - 0 1".to_owned(), 0.0))
+        );
+        assert_eq!(parse_line("4k3/8/8/8/8/8/8/R3K3 w -"), None);
+        assert_eq!(parse_line("4k3/8/8/8/8/8/8/R3K3 w - - weight -1;"), None);
+        assert_eq!(parse_line("4k3/8/8/8/8/8/8/R3K3 w - - weight;"), None);
+    }
+
+    #[test]
+    fn sample_follows_weights() {
+        let ruta = temp_path("start_positions.epd");
+        fs::write(
+            &ruta,
+            "# finales\n4k3/8/8/8/8/8/8/R3K3 w - - weight 3;\n\n4k3/8/8/8/8/8/8/Q3K3 w - - 10 50 weight 1;\n",
+        )
+        .unwrap();
+        let config = StartPositionsConfig { path: ruta.to_string_lossy().into_owned(), initial_weight: 0.0 };
+        let posiciones = StartPositions::load(&config);
+        fs::remove_file(&ruta).unwrap();
+        let posiciones = posiciones.unwrap().unwrap();
+
+        let mut rng = ChaCha8Rng::seed_from_u64(3);
+        let n = 4000;
+        let mut torres = 0;
+        for _ in 0..n {
+            let game_manager = posiciones.sample(&mut rng);
+            match game_manager.initial_fen().as_str() {
+                "4k3/8/8/8/8/8/8/R3K3 w - - 0 1" => torres += 1,
+                fen => assert_eq!(fen, "4k3/8/8/8/8/8/8/Q3K3 w - - 10 50"),
+            }
+        }
+
+        let frecuencia = torres as f64 / n as f64;
+        assert!((frecuencia - 0.75).abs() < 0.03, "{frecuencia}");
+    }
+
+    #[test]
+    fn load_rejects_invalid_positions() {
+        for (nombre, contenido) in [
+            ("sin_rey.epd", "8/8/8/8/8/8/8/R3K3 w - -\n"),
+            ("mate.epd", "k7/1Q6/1K6/8/8/8/8/8 b - -\n"),
+            ("sin_peso.epd", "4k3/8/8/8/8/8/8/R3K3 w - - weight 0;\n"),
+        ] {
+            let ruta = temp_path(nombre);
+            fs::write(&ruta, contenido).unwrap();
+            let config = StartPositionsConfig { path: ruta.to_string_lossy().into_owned(), initial_weight: 0.0 };
+            let resultado = StartPositions::load(&config);
+            fs::remove_file(&ruta).unwrap();
+            assert!(resultado.is_err(), "{nombre}");
+        }
+    }
+}
@@ -0,0 +1,204 @@
+use std::io::{self, Read, Write};
+
+use chess::{Board, CastleRights, Color, ALL_PIECES};
+use serde::{Deserialize, Serialize};
+use tch::Tensor;
+
+use crate::binary_io::{read_u64, read_u8, write_u64, write_u8};
+
+// Posicion empaquetada para el replay buffer: se codifica directamente a tensor sin pasar por FEN
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct PackedState {
+    // Bitboards por color y pieza: indice color * 6 + pieza
+    pub pieces: [u64; 12],
+    // 0 blancas, 1 negras
+    pub side_to_move: u8,
+    // Bits 0-1 derechos de las blancas, bits 2-3 de las negras (CastleRights::to_index)
+    pub castle_rights: u8,
+    // Casilla del peon capturable al paso, 64 si no hay
+    pub en_passant: u8,
+    // Medias jugadas desde la ultima captura o movimiento de peon (saturado a 255)
+    pub halfmove: u8,
+    // Veces que la posicion ya se habia dado antes
+    pub repetitions: u8,
+}
+
+pub const NO_EN_PASSANT: u8 = 64;
+
+// Como se convierte una posicion en la entrada de la red
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum StateEncoding {
+    // Un plano 9x8: piezas con signo por color y una fila extra con turno y enroques
+    #[default]
+    Compact,
+    // Planos 8x8: 12 de piezas, turno, 4 de enroque, al paso, regla de 50 y repeticiones
+    Planes,
+}
+
+const NUM_PLANES: usize = 20;
+const SIDE_PLANE: usize = 12;
+const CASTLE_PLANE: usize = 13;
+const EN_PASSANT_PLANE: usize = 17;
+const HALFMOVE_PLANE: usize = 18;
+const REPETITION_PLANE: usize = 19;
+
+impl StateEncoding {
+    pub fn channels(self) -> i64 {
+        match self {
+            StateEncoding::Compact => 1,
+            StateEncoding::Planes => NUM_PLANES as i64,
+        }
+    }
+
+    pub fn height(self) -> i64 {
+        match self {
+            StateEncoding::Compact => 9,
+            StateEncoding::Planes => 8,
+        }
+    }
+
+    pub fn size(self) -> usize {
+        (self.channels() * self.height() * 8) as usize
+    }
+}
+
+impl PackedState {
+    pub fn from_board(board: &Board) -> PackedState {
+        let mut pieces = [0u64; 12];
+
+        for color in [Color::White, Color::Black] {
+            for piece in ALL_PIECES {
+                pieces[color.to_index() * 6 + piece.to_index()] =
+                    (board.pieces(piece) & board.color_combined(color)).0;
+            }
+        }
+
+        PackedState {
+            pieces,
+            side_to_move: board.side_to_move().to_index() as u8,
+            castle_rights: (board.castle_rights(Color::White).to_index()
+                | board.castle_rights(Color::Black).to_index() << 2) as u8,
+            en_passant: match board.en_passant() {
+                Some(square) => square.to_index() as u8,
+                None => NO_EN_PASSANT,
+            },
+            halfmove: 0,
+            repetitions: 0,
+        }
+    }
+
+    // Con el historial de la partida (ver GameManager::packed_state)
+    pub fn with_history(mut self, halfmove: u32, repetitions: u32) -> PackedState {
+        self.halfmove = halfmove.min(u8::MAX as u32) as u8;
+        self.repetitions = repetitions.min(u8::MAX as u32) as u8;
+        self
+    }
+
+    pub fn castle_rights(&self, color: Color) -> CastleRights {
+        CastleRights::from_index(match color {
+            Color::White => self.castle_rights & 0b11,
+            Color::Black => (self.castle_rights >> 2) & 0b11,
+        } as usize)
+    }
+
+    pub fn write_to<W: Write>(&self, w: &mut W) -> io::Result<()> {
+        for bitboard in self.pieces.iter() {
+            write_u64(w, *bitboard)?;
+        }
+        write_u8(w, self.side_to_move)?;
+        write_u8(w, self.castle_rights)?;
+        write_u8(w, self.en_passant)?;
+        write_u8(w, self.halfmove)?;
+        write_u8(w, self.repetitions)
+    }
+
+    pub fn read_from<R: Read>(r: &mut R) -> io::Result<PackedState> {
+        let mut pieces = [0u64; 12];
+        for bitboard in pieces.iter_mut() {
+            *bitboard = read_u64(r)?;
+        }
+
+        Ok(PackedState {
+            pieces,
+            side_to_move: read_u8(r)?,
+            castle_rights: read_u8(r)?,
+            en_passant: read_u8(r)?,
+            halfmove: read_u8(r)?,
+            repetitions: read_u8(r)?,
+        })
+    }
+
+    fn write(&self, encoding: StateEncoding, out: &mut [f32]) {
+        match encoding {
+            StateEncoding::Compact => self.write_compact(out),
+            StateEncoding::Planes => self.write_planes(out),
+        }
+    }
+
+    fn write_compact(&self, out: &mut [f32]) {
+        for (i, bitboard) in self.pieces.iter().enumerate() {
+            let valor = (i % 6 + 1) as f32 * if i < 6 { 1.0 } else { -1.0 };
+            let mut bits = *bitboard;
+
+            while bits != 0 {
+                let square = bits.trailing_zeros() as usize;
+                out[square] = valor;
+                bits &= bits - 1;
+            }
+        }
+
+        out[64] = if self.side_to_move == 0 { 1.0 } else { -1.0 };
+        out[65] = self.castle_rights(Color::White).to_index() as f32;
+        out[66] = self.castle_rights(Color::Black).to_index() as f32;
+    }
+
+    fn write_planes(&self, out: &mut [f32]) {
+        for (i, bitboard) in self.pieces.iter().enumerate() {
+            let mut bits = *bitboard;
+
+            while bits != 0 {
+                out[i * 64 + bits.trailing_zeros() as usize] = 1.0;
+                bits &= bits - 1;
+            }
+        }
+
+        let white = self.side_to_move == 0;
+        fill_plane(out, SIDE_PLANE, if white { 1.0 } else { 0.0 });
+
+        let enroques = [
+            self.castle_rights(Color::White).has_kingside(),
+            self.castle_rights(Color::White).has_queenside(),
+            self.castle_rights(Color::Black).has_kingside(),
+            self.castle_rights(Color::Black).has_queenside(),
+        ];
+        for (i, enroque) in enroques.iter().enumerate() {
+            fill_plane(out, CASTLE_PLANE + i, if *enroque { 1.0 } else { 0.0 });
+        }
+
+        // Se marca la casilla de destino de la captura, detras del peon que acaba de avanzar
+        if self.en_passant != NO_EN_PASSANT {
+            let destino = if white { self.en_passant as usize + 8 } else { self.en_passant as usize - 8 };
+            out[EN_PASSANT_PLANE * 64 + destino] = 1.0;
+        }
+
+        fill_plane(out, HALFMOVE_PLANE, f32::min(self.halfmove as f32 / 100.0, 1.0));
+        fill_plane(out, REPETITION_PLANE, f32::min(self.repetitions as f32 / 2.0, 1.0));
+    }
+}
+
+fn fill_plane(out: &mut [f32], plane: usize, valor: f32) {
+    out[plane * 64..(plane + 1) * 64].fill(valor);
+}
+
+// Un unico tensor [batch, encoding.size()] para todo el lote
+pub fn batch_to_tensor(states: &[&PackedState], encoding: StateEncoding) -> Tensor {
+    let size = encoding.size();
+    let mut data = vec![0.0f32; states.len() * size];
+
+    for (state, out) in states.iter().zip(data.chunks_mut(size)) {
+        state.write(encoding, out);
+    }
+
+    Tensor::from_slice(data.as_slice()).view([states.len() as i64, size as i64])
+}
@@ -0,0 +1,94 @@
+// Arbol de sumas sobre un array de prioridades: actualizacion y busqueda por prefijo en O(log n)
+pub struct SumTree {
+    size: usize,
+    tree: Vec<f64>,
+}
+
+impl SumTree {
+    pub fn new(capacity: usize) -> SumTree {
+        let size = capacity.max(1).next_power_of_two();
+        SumTree { size, tree: vec![0.0; 2 * size] }
+    }
+
+    pub fn set(&mut self, index: usize, priority: f64) {
+        let mut node = index + self.size;
+        self.tree[node] = priority;
+
+        while node > 1 {
+            node /= 2;
+            self.tree[node] = self.tree[2 * node] + self.tree[2 * node + 1];
+        }
+    }
+
+    pub fn get(&self, index: usize) -> f64 {
+        self.tree[index + self.size]
+    }
+
+    pub fn total(&self) -> f64 {
+        self.tree[1]
+    }
+
+    // Indice de la hoja donde cae `value` al acumular las prioridades de izquierda a derecha
+    pub fn find(&self, mut value: f64) -> usize {
+        let mut node = 1;
+
+        while node < self.size {
+            let left = 2 * node;
+            if value < self.tree[left] {
+                node = left;
+            }
+            else {
+                value -= self.tree[left];
+                node = left + 1;
+            }
+        }
+
+        node - self.size
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn arbol(prioridades: &[f64]) -> SumTree {
+        let mut tree = SumTree::new(prioridades.len());
+        for (i, p) in prioridades.iter().enumerate() {
+            tree.set(i, *p);
+        }
+        tree
+    }
+
+    #[test]
+    fn find_respects_prefix_boundaries() {
+        // Prefijos: [0, 1) -> 0, [1, 3) -> 1, [3, 6) -> 2, [6, 10) -> 3
+        let tree = arbol(&[1.0, 2.0, 3.0, 4.0]);
+
+        for (valor, hoja) in [(0.0, 0), (0.999, 0), (1.0, 1), (2.999, 1), (3.0, 2), (5.999, 2), (6.0, 3), (9.999, 3)] {
+            assert_eq!(tree.find(valor), hoja, "valor {valor}");
+        }
+    }
+
+    #[test]
+    fn find_skips_empty_leaves() {
+        // Capacidad 5: las hojas de relleno hasta 8 tienen prioridad 0
+        let tree = arbol(&[1.0, 0.0, 0.0, 2.0, 1.0]);
+
+        assert_eq!(tree.find(1.0), 3);
+        assert_eq!(tree.find(3.0), 4);
+        assert_eq!(tree.find(tree.total() - 1e-9), 4);
+    }
+
+    #[test]
+    fn set_keeps_total_consistent() {
+        let mut tree = arbol(&[0.5; 7]);
+        assert_eq!(tree.total(), 3.5);
+
+        for (i, p) in [(3, 2.0), (0, 0.0), (6, 1.25), (3, 0.25)] {
+            tree.set(i, p);
+            let suma: f64 = (0..7).map(|j| tree.get(j)).sum();
+            assert!((tree.total() - suma).abs() < 1e-12);
+        }
+        assert_eq!(tree.get(3), 0.25);
+    }
+}
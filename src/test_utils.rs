@@ -0,0 +1,26 @@
+use std::{env, path::PathBuf, process};
+
+use chess::Board;
+
+use crate::{replay_buffer::BufferElement, state_encoding::PackedState};
+
+// Transicion de prueba desde la posicion inicial. id va en la accion y en next_legal_actions
+// para poder seguir de que transicion sale cada campo.
+pub fn transition(reward: f32, id: u16, done: bool) -> BufferElement {
+    let state = PackedState::from_board(&Board::default());
+
+    BufferElement {
+        actual_state: state,
+        action: id as usize,
+        reward,
+        next_state: state,
+        next_legal_actions: vec![id],
+        done,
+        next_value: None,
+    }
+}
+
+// Fichero temporal propio del proceso para las pruebas de guardado y carga
+pub fn temp_path(nombre: &str) -> PathBuf {
+    env::temp_dir().join(format!("dqn_chess_{}_{nombre}", process::id()))
+}
@@ -0,0 +1,318 @@
+use std::{fs, path::{Path, PathBuf}};
+
+use clap::ValueEnum;
+use serde::{Deserialize, Serialize};
+
+use crate::{checkpoint::Checkpoint, curriculum::CurriculumConfig, dqn_nn_model::ModelConfig, mcts::MctsConfig};
+
+// Como se calcula el valor de arranque del TD target en self-play
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ValueEnum)]
+#[serde(rename_all = "snake_case")]
+pub enum TargetMode {
+    // r + gamma * max Q(s'), ignorando que s' es turno del rival
+    Standard,
+    // r - gamma * max Q(s'): el valor del rival es el nuestro cambiado de signo
+    Negamax,
+    // r + gamma * max Q(s''), con s'' la posicion tras la respuesta del rival
+    OpponentReply,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ReplayConfig {
+    // Prioritized experience replay en lugar de muestreo uniforme
+    pub prioritized: bool,
+    pub alpha: f64,
+    pub beta_start: f64,
+    // Muestreos hasta que beta llega a 1
+    pub beta_steps: u32,
+    pub priority_epsilon: f64,
+    // Fichero binario del buffer: se guarda junto a la red y puede recuperarse al reanudar
+    pub buffer_path: String,
+    pub save_buffer: bool,
+    pub resume_buffer: bool,
+}
+
+impl Default for ReplayConfig {
+    fn default() -> Self {
+        ReplayConfig {
+            prioritized: false,
+            alpha: 0.6,
+            beta_start: 0.4,
+            beta_steps: 100000,
+            priority_epsilon: 1e-5,
+            buffer_path: "replay.bin".to_owned(),
+            save_buffer: false,
+            resume_buffer: false,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct CheckpointConfig {
+    // Directorio con el estado completo del entrenamiento
+    pub dir: String,
+    // Cada cuantas partidas se escribe; con 0 solo al terminar
+    pub every_games: u32,
+    pub resume: bool,
+}
+
+impl Default for CheckpointConfig {
+    fn default() -> Self {
+        CheckpointConfig {
+            dir: "checkpoint".to_owned(),
+            every_games: 100,
+            resume: false,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct PgnConfig {
+    // Fichero al que se anaden las partidas de self-play muestreadas
+    pub path: String,
+    // Se guarda una de cada every_games partidas; con 0 ninguna
+    pub every_games: u32,
+}
+
+impl Default for PgnConfig {
+    fn default() -> Self {
+        PgnConfig {
+            path: "self_play.pgn".to_owned(),
+            every_games: 100,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct StartPositionsConfig {
+    // Fichero FEN/EPD con las posiciones de salida; vacio para empezar siempre desde la inicial
+    pub path: String,
+    // Peso de la posicion inicial frente a las del fichero
+    pub initial_weight: f64,
+}
+
+impl Default for StartPositionsConfig {
+    fn default() -> Self {
+        StartPositionsConfig {
+            path: String::new(),
+            initial_weight: 0.0,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct TrainConfig {
+    pub model_path: String,
+    pub num_games: u32,
+    // Semilla del generador aleatorio; sin ella se elige una al azar
+    pub seed: Option<u64>,
+
+    // Calculo de epsilon
+    pub init_epsilon: f64,
+    pub final_epsilon: f64,
+
+    // replayBuffer
+    pub buffer_capacity: usize,
+    pub gamma: f64,
+    pub target_mode: TargetMode,
+    pub double_dqn: bool,
+    // Pasos del retorno; el target arranca con gamma^n
+    pub n_step: usize,
+
+    // entrenamiento de target network
+    pub warmup_size: u32,
+    pub train_frequency: u32,
+    pub batch_size: usize,
+    pub target_update_freq: u32,
+
+    // optimizador
+    pub learning_rate: f64,
+    pub weight_decay: f64,
+
+    pub model: ModelConfig,
+    pub replay: ReplayConfig,
+    pub checkpoint: CheckpointConfig,
+    pub mcts: MctsConfig,
+    pub pgn: PgnConfig,
+    pub start_positions: StartPositionsConfig,
+    pub curriculum: CurriculumConfig,
+}
+
+impl Default for TrainConfig {
+    fn default() -> Self {
+        TrainConfig {
+            model_path: "nn.pth".to_owned(),
+            num_games: 10000,
+            seed: None,
+            init_epsilon: 1.0,
+            final_epsilon: 0.01,
+            buffer_capacity: 500000,
+            gamma: 0.99,
+            target_mode: TargetMode::Standard,
+            double_dqn: false,
+            n_step: 1,
+            warmup_size: 35000,
+            train_frequency: 10,
+            batch_size: 16,
+            target_update_freq: 10000,
+            learning_rate: 0.001,
+            weight_decay: 0.01,
+            model: ModelConfig::default(),
+            replay: ReplayConfig::default(),
+            checkpoint: CheckpointConfig::default(),
+            mcts: MctsConfig::default(),
+            pgn: PgnConfig::default(),
+            start_positions: StartPositionsConfig::default(),
+            curriculum: CurriculumConfig::default(),
+        }
+    }
+}
+
+impl TrainConfig {
+    // Formato segun la extension: .json o TOML en cualquier otro caso
+    pub fn load<P: AsRef<Path>>(ruta: P) -> Result<TrainConfig, String> {
+        let ruta = ruta.as_ref();
+        let contenido = fs::read_to_string(ruta)
+            .map_err(|e| format!("No se pudo leer {}: {e}", ruta.display()))?;
+
+        if is_json(ruta) {
+            serde_json::from_str(&contenido).map_err(|e| format!("Configuracion invalida en {}: {e}", ruta.display()))
+        }
+        else {
+            toml::from_str(&contenido).map_err(|e| format!("Configuracion invalida en {}: {e}", ruta.display()))
+        }
+    }
+
+    pub fn save<P: AsRef<Path>>(&self, ruta: P) -> Result<(), String> {
+        let ruta = ruta.as_ref();
+        let contenido = if is_json(ruta) {
+            serde_json::to_string_pretty(self).map_err(|e| e.to_string())?
+        }
+        else {
+            toml::to_string_pretty(self).map_err(|e| e.to_string())?
+        };
+
+        fs::write(ruta, contenido).map_err(|e| format!("No se pudo escribir {}: {e}", ruta.display()))
+    }
+
+    pub fn validate(&self) -> Result<(), String> {
+        let mut errores: Vec<String> = Vec::new();
+
+        if self.model_path.trim().is_empty() {
+            errores.push("model_path no puede estar vacio".to_owned());
+        }
+        if self.num_games == 0 {
+            errores.push("num_games debe ser mayor que 0".to_owned());
+        }
+        if !(0.0..=1.0).contains(&self.init_epsilon) || !(0.0..=1.0).contains(&self.final_epsilon) {
+            errores.push("init_epsilon y final_epsilon deben estar en [0, 1]".to_owned());
+        }
+        if self.final_epsilon > self.init_epsilon {
+            errores.push("final_epsilon no puede ser mayor que init_epsilon".to_owned());
+        }
+        if !(self.gamma > 0.0 && self.gamma <= 1.0) {
+            errores.push("gamma debe estar en (0, 1]".to_owned());
+        }
+        if self.n_step == 0 {
+            errores.push("n_step debe ser mayor que 0".to_owned());
+        }
+        if self.batch_size == 0 {
+            errores.push("batch_size debe ser mayor que 0".to_owned());
+        }
+        if self.buffer_capacity < self.batch_size {
+            errores.push("buffer_capacity debe ser al menos batch_size".to_owned());
+        }
+        if (self.warmup_size as usize) < self.batch_size {
+            errores.push("warmup_size debe ser al menos batch_size".to_owned());
+        }
+        if self.warmup_size as usize > self.buffer_capacity {
+            errores.push("warmup_size no puede superar buffer_capacity".to_owned());
+        }
+        if self.train_frequency == 0 {
+            errores.push("train_frequency debe ser mayor que 0".to_owned());
+        }
+        if self.target_update_freq == 0 {
+            errores.push("target_update_freq debe ser mayor que 0".to_owned());
+        }
+        if self.learning_rate <= 0.0 {
+            errores.push("learning_rate debe ser mayor que 0".to_owned());
+        }
+        if self.weight_decay < 0.0 {
+            errores.push("weight_decay no puede ser negativo".to_owned());
+        }
+
+        if self.replay.alpha < 0.0 {
+            errores.push("replay.alpha no puede ser negativo".to_owned());
+        }
+        if !(0.0..=1.0).contains(&self.replay.beta_start) {
+            errores.push("replay.beta_start debe estar en [0, 1]".to_owned());
+        }
+        if self.replay.priority_epsilon <= 0.0 {
+            errores.push("replay.priority_epsilon debe ser mayor que 0".to_owned());
+        }
+
+        if (self.replay.save_buffer || self.replay.resume_buffer) && self.replay.buffer_path.trim().is_empty() {
+            errores.push("replay.buffer_path no puede estar vacio".to_owned());
+        }
+
+        errores.extend(self.model.validate());
+        errores.extend(self.mcts.validate());
+        errores.extend(self.curriculum.validate());
+
+        if self.checkpoint.dir.trim().is_empty() {
+            errores.push("checkpoint.dir no puede estar vacio".to_owned());
+        }
+
+        if self.pgn.every_games > 0 && self.pgn.path.trim().is_empty() {
+            errores.push("pgn.path no puede estar vacio".to_owned());
+        }
+
+        if self.start_positions.initial_weight < 0.0 {
+            errores.push("start_positions.initial_weight no puede ser negativo".to_owned());
+        }
+
+        if errores.is_empty() {
+            Ok(())
+        }
+        else {
+            Err(errores.join("\n"))
+        }
+    }
+
+    // Copia de la configuracion efectiva junto al checkpoint: nn.pth -> nn.train.toml.
+    // Un nombre propio para no pisar el fichero de --config del usuario (nn.toml).
+    pub fn dump_path(&self) -> PathBuf {
+        dump_path_for(Path::new(&self.model_path))
+    }
+}
+
+fn is_json(ruta: &Path) -> bool {
+    ruta.extension().is_some_and(|e| e.eq_ignore_ascii_case("json"))
+}
+
+fn dump_path_for(ruta_nn: &Path) -> PathBuf {
+    ruta_nn.with_extension("train.toml")
+}
+
+// Arquitectura con la que se entreno una red y fichero del que se leyo. Se busca el volcado de train
+// (nn.train.toml), el de versiones anteriores (nn.toml) y el config.toml del checkpoint.
+// None si no hay ninguno: la red se carga con la arquitectura por defecto.
+pub fn model_config_for(ruta_nn: &Path) -> Result<Option<(ModelConfig, PathBuf)>, String> {
+    let candidatos = [dump_path_for(ruta_nn), ruta_nn.with_extension("toml")]
+        .into_iter()
+        .chain(Checkpoint::config_for_network(ruta_nn));
+
+    for ruta_config in candidatos {
+        if ruta_config.exists() {
+            let config = TrainConfig::load(&ruta_config)?;
+            return Ok(Some((config.model, ruta_config)));
+        }
+    }
+
+    Ok(None)
+}